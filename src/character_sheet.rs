@@ -0,0 +1,272 @@
+use bracket_terminal::prelude::*;
+
+use veloren_common::comp::{
+    skills::{Skill, SkillGroupKind, SKILL_GROUP_DEFS, SKILL_PREREQUISITES},
+    Body, SkillSet,
+};
+
+use crate::{
+    hud,
+    keymap::{Action, Keymap},
+};
+
+/// Width of the skill group column
+const GROUP_WIDTH: i32 = 40;
+
+/// Cursor on the character screen: the selected skill group and a skill within it
+#[derive(Default)]
+pub struct CharacterSheet {
+    group: usize,
+    skill: usize,
+}
+
+/// Skill groups the character has, in the order the server lists them
+fn groups(skill_set: &SkillSet) -> Vec<SkillGroupKind> {
+    skill_set
+        .skill_groups
+        .iter()
+        .map(|group| group.skill_group_kind)
+        .collect()
+}
+
+/// Skills of the same group `skill` needs first
+fn prerequisites(skill: Skill, group: &[Skill]) -> Vec<Skill> {
+    SKILL_PREREQUISITES
+        .get(&skill)
+        .map(|needs| {
+            needs
+                .keys()
+                .copied()
+                .filter(|need| group.contains(need))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// How many skills of the same group are needed before `skill`, one after another
+fn depth(skill: Skill, group: &[Skill]) -> usize {
+    prerequisites(skill, group)
+        .into_iter()
+        .map(|need| depth(need, group) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Every skill of a group as a tree with how deep in it each is, skills coming right after the
+/// deepest skill they need and sorted by name so the list doesn't jump around
+fn skills(group: SkillGroupKind) -> Vec<(Skill, usize)> {
+    let mut all: Vec<Skill> = SKILL_GROUP_DEFS
+        .get(&group)
+        .map(|def| def.skills.iter().copied().collect())
+        .unwrap_or_default();
+    all.sort_by_key(|skill| format!("{:?}", skill));
+    let parent = |skill: Skill| {
+        prerequisites(skill, &all)
+            .into_iter()
+            .max_by_key(|need| (depth(*need, &all), format!("{:?}", need)))
+    };
+
+    fn visit(
+        skill: Skill,
+        depth: usize,
+        all: &[Skill],
+        parent: &dyn Fn(Skill) -> Option<Skill>,
+        tree: &mut Vec<(Skill, usize)>,
+    ) {
+        tree.push((skill, depth));
+        for child in all.iter().filter(|child| parent(**child) == Some(skill)) {
+            visit(*child, depth + 1, all, parent, tree);
+        }
+    }
+    let mut tree = Vec::with_capacity(all.len());
+    for root in all.iter().filter(|skill| parent(**skill).is_none()) {
+        visit(*root, 0, &all, &parent, &mut tree);
+    }
+    tree
+}
+
+/// Whether every skill `skill` needs is unlocked far enough
+fn can_unlock(skill: Skill, skill_set: &SkillSet) -> bool {
+    SKILL_PREREQUISITES.get(&skill).map_or(true, |needs| {
+        needs.iter().all(|(need, level)| {
+            skill_set
+                .skills
+                .get(need)
+                .map_or(false, |unlocked| unlocked >= level)
+        })
+    })
+}
+
+impl CharacterSheet {
+    /// Move the cursor with the menu actions, returns the skill to spend a point on when
+    /// `MenuSelect` is pressed
+    pub fn handle_action(&mut self, action: Action, skill_set: &SkillSet) -> Option<Skill> {
+        let groups = groups(skill_set);
+        match action {
+            Action::MenuLeft => {
+                self.group = self.group.saturating_sub(1);
+                self.skill = 0;
+            }
+            Action::MenuRight => {
+                self.group = (self.group + 1).min(groups.len().saturating_sub(1));
+                self.skill = 0;
+            }
+            Action::MenuUp => self.skill = self.skill.saturating_sub(1),
+            Action::MenuDown => self.skill += 1,
+            Action::MenuSelect => {
+                return groups
+                    .get(self.group)
+                    .and_then(|group| skills(*group).get(self.skill).map(|(skill, _)| *skill));
+            }
+            _ => {}
+        }
+        if let Some(group) = groups.get(self.group) {
+            self.skill = self.skill.min(skills(*group).len().saturating_sub(1));
+        }
+        None
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut BTerm,
+        rect: Rect,
+        body: Option<Body>,
+        skill_set: &SkillSet,
+        keymap: &Keymap,
+    ) {
+        ctx.draw_box(
+            rect.x1,
+            rect.y1,
+            rect.width() - 1,
+            rect.height() - 1,
+            RGB::named(WHITE),
+            RGB::named(BLACK),
+        );
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y1,
+            RGB::named(PINK),
+            RGB::named(BLACK),
+            " Character ",
+        );
+
+        let species = match body {
+            Some(Body::Humanoid(humanoid)) => format!("{:?}", humanoid.species),
+            Some(body) => format!("{:?}", body),
+            None => "?".to_string(),
+        };
+        ctx.printer(
+            rect.x1 + 2,
+            rect.y1 + 2,
+            &format!("#[pink]Species: #[]{}", species),
+            TextAlign::Left,
+            None,
+        );
+
+        // Skill groups with their progress on the left
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y1 + 4,
+            RGB::named(PINK),
+            RGB::named(BLACK),
+            "Skill group       Earned  SP  Exp",
+        );
+        for (i, group) in skill_set.skill_groups.iter().enumerate() {
+            let line = format!(
+                "{:<17} {:>6} {:>3}  {}/{}",
+                hud::fit(&format!("{:?}", group.skill_group_kind), 17),
+                group.earned_sp,
+                group.available_sp,
+                group.exp,
+                group.skill_group_kind.skill_point_cost(group.earned_sp),
+            );
+            let (fg, bg) = if i == self.group {
+                (RGB::named(BLACK), RGB::named(WHITE))
+            } else {
+                (RGB::named(WHITE), RGB::named(BLACK))
+            };
+            ctx.print_color(
+                rect.x1 + 2,
+                rect.y1 + 5 + i as i32,
+                fg,
+                bg,
+                &hud::fit(&line, GROUP_WIDTH - 2),
+            );
+        }
+
+        // Skill tree of the selected group on the right
+        let x = rect.x1 + GROUP_WIDTH + 1;
+        let rows = (rect.height() - 8).max(0) as usize;
+        if let Some(group) = groups(skill_set).get(self.group) {
+            ctx.print_color(x, rect.y1 + 4, RGB::named(PINK), RGB::named(BLACK), "Skill");
+            let first = (self.skill + 1).saturating_sub(rows);
+            for (i, (skill, depth)) in skills(*group).iter().enumerate().skip(first).take(rows) {
+                // Unlocked skills are in the map, with a level if they have more than one
+                let state = match skill_set.skills.get(skill) {
+                    Some(Some(level)) => format!("[{}]", level),
+                    Some(None) => "[x]".to_string(),
+                    None => "[ ]".to_string(),
+                };
+                let (fg, bg) = if i == self.skill {
+                    (RGB::named(BLACK), RGB::named(WHITE))
+                } else if skill_set.skills.contains_key(skill) {
+                    (RGB::named(GREEN), RGB::named(BLACK))
+                } else if can_unlock(*skill, skill_set) {
+                    (RGB::named(WHITE), RGB::named(BLACK))
+                } else {
+                    (RGB::named(GREY), RGB::named(BLACK))
+                };
+                // Indented under the skill they need
+                ctx.print_color(
+                    x,
+                    rect.y1 + 5 + (i - first) as i32,
+                    fg,
+                    bg,
+                    &hud::fit(
+                        &format!("{}{} {:?}", "  ".repeat(*depth), state, skill),
+                        rect.x2 - 2 - x,
+                    ),
+                );
+            }
+        }
+
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y2 - 2,
+            RGB::named(GREY),
+            RGB::named(BLACK),
+            &hud::fit(
+                &format!(
+                    "{}/{} - Group  {}/{} - Skill  {} - Spend point  {} - Close",
+                    keymap.label(Action::MenuLeft),
+                    keymap.label(Action::MenuRight),
+                    keymap.label(Action::MenuUp),
+                    keymap.label(Action::MenuDown),
+                    keymap.label(Action::MenuSelect),
+                    keymap.label(Action::CharacterSheet),
+                ),
+                rect.width() - 4,
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skills_come_after_the_skills_they_need() {
+        for (group, def) in SKILL_GROUP_DEFS.iter() {
+            let tree = skills(*group);
+            assert_eq!(tree.len(), def.skills.len());
+            let all: Vec<_> = tree.iter().map(|(skill, _)| *skill).collect();
+            for (i, (skill, depth)) in tree.iter().enumerate() {
+                for need in prerequisites(*skill, &all) {
+                    assert!(all[..i].contains(&need), "{:?} before {:?}", need, skill);
+                }
+                assert_eq!(*depth, super::depth(*skill, &all));
+            }
+        }
+    }
+}
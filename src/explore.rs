@@ -0,0 +1,72 @@
+use vek::*;
+
+use veloren_common::terrain::TerrainGrid;
+
+use crate::{memory::MapMemory, pathfinding};
+
+/// Hostiles within this many blocks of the player stop exploring
+pub const SIGHT_RADIUS: i32 = 24;
+/// How far from the player to look for somewhere new to go
+const SEARCH_RADIUS: i32 = SIGHT_RADIUS + 16;
+/// Columns with nothing remembered this close count as unexplored, zoomed out maps only
+/// remember some of the columns they show
+const UNSEEN_RADIUS: i32 = 2;
+/// Unexplored columns closer than this to a hostile are left alone
+const AVOID_RADIUS: f32 = 16.0;
+/// Unexplored columns tried before giving up, nearest first
+const MAX_TRIES: usize = 4;
+/// Blocks between the unexplored columns tried
+const TRY_SPACING: i32 = 8;
+
+/// Whether nothing is remembered around a column, so it's never been on screen
+fn is_unexplored(memory: &MapMemory, wpos: Vec2<i32>) -> bool {
+    (-UNSEEN_RADIUS..=UNSEEN_RADIUS).all(|y| {
+        (-UNSEEN_RADIUS..=UNSEEN_RADIUS).all(|x| memory.get(wpos + Vec2::new(x, y)).is_none())
+    })
+}
+
+/// Path from `start` to the nearest column not in `memory` it can walk to without swimming or
+/// going near `hostiles`
+pub fn find_unexplored(
+    memory: &MapMemory,
+    terrain: &TerrainGrid,
+    start: Vec3<i32>,
+    hostiles: &[Vec3<f32>],
+) -> Option<Vec<Vec3<i32>>> {
+    let mut candidates = Vec::new();
+    for y in -SEARCH_RADIUS..=SEARCH_RADIUS {
+        for x in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            let wpos = Vec2::from(start) + Vec2::new(x, y);
+            let near_hostile = hostiles
+                .iter()
+                .any(|pos| Vec2::from(*pos).distance(wpos.map(|e| e as f32 + 0.5)) < AVOID_RADIUS);
+            if !near_hostile
+                && terrain.get_key(TerrainGrid::chunk_key(wpos)).is_some()
+                && is_unexplored(memory, wpos)
+            {
+                candidates.push((x * x + y * y, wpos));
+            }
+        }
+    }
+    candidates.sort_by_key(|(dist, _)| *dist);
+
+    // Try places in different directions, in case one of them is cut off
+    let mut tried: Vec<Vec2<i32>> = Vec::new();
+    for (_, wpos) in candidates {
+        if tried.len() >= MAX_TRIES {
+            break;
+        }
+        if tried
+            .iter()
+            .any(|other| (*other - wpos).map(|e| e * e).sum() < TRY_SPACING * TRY_SPACING)
+        {
+            continue;
+        }
+        tried.push(wpos);
+        let goal = Vec3::new(wpos.x, wpos.y, start.z);
+        if let Some(path) = pathfinding::find_path(terrain, start, goal, false) {
+            return Some(path);
+        }
+    }
+    None
+}
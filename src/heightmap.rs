@@ -0,0 +1,311 @@
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use vek::*;
+
+use veloren_common::{
+    terrain::{TerrainChunkSize, TerrainGrid},
+    vol::RectVolSize,
+};
+use veloren_common_state::TerrainChanges;
+
+use crate::render::{self, Cell};
+
+/// How many heights a chunk's columns are kept for, enough to walk over hills and jump without
+/// scanning everything again
+const KEPT_HEIGHTS: usize = 8;
+/// How the player's height changes from frame to frame in `benchmark`, like a jump
+const BENCHMARK_HEIGHTS: [i32; 6] = [0, 1, 2, 3, 2, 1];
+
+/// Scanned columns of a single chunk, all from the same height
+struct ChunkColumns {
+    z: i32,
+    cells: Vec<Option<Cell>>,
+}
+
+impl ChunkColumns {
+    fn new(z: i32) -> Self {
+        Self {
+            z,
+            cells: vec![
+                None;
+                (TerrainChunkSize::RECT_SIZE.x * TerrainChunkSize::RECT_SIZE.y) as usize
+            ],
+        }
+    }
+}
+
+/// Per-chunk cache of what `render::scan_column` found for each column
+///
+/// A column is scanned relative to the player's height, so each chunk keeps its columns for
+/// the last `KEPT_HEIGHTS` heights they were asked for from, until the chunk changes.
+#[derive(Default)]
+pub struct HeightmapCache {
+    chunks: HashMap<Vec2<i32>, Vec<ChunkColumns>>,
+}
+
+impl HeightmapCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop cached columns of every chunk that was loaded, changed or unloaded this tick
+    pub fn invalidate(&mut self, changes: &TerrainChanges) {
+        for key in changes
+            .new_chunks
+            .iter()
+            .chain(changes.modified_chunks.iter())
+            .chain(changes.removed_chunks.iter())
+        {
+            self.chunks.remove(key);
+        }
+        for pos in changes.modified_blocks.keys() {
+            self.chunks.remove(&TerrainGrid::chunk_key(*pos));
+        }
+    }
+
+    /// Cached column at `wpos`, or `None` if it has to be scanned
+    pub fn lookup(&self, wpos: Vec3<i32>) -> Option<Cell> {
+        self.chunks
+            .get(&TerrainGrid::chunk_key(wpos))
+            .and_then(|heights| heights.iter().find(|chunk| chunk.z == wpos.z))
+            .and_then(|chunk| chunk.cells[column_index(wpos)])
+    }
+
+    /// Remember the column scanned at `wpos`, forgetting the chunk's oldest height if it has
+    /// too many
+    pub fn insert(&mut self, wpos: Vec3<i32>, cell: Cell) {
+        let heights = self.chunks.entry(TerrainGrid::chunk_key(wpos)).or_default();
+        let i = match heights.iter().position(|chunk| chunk.z == wpos.z) {
+            Some(i) => i,
+            None => {
+                if heights.len() >= KEPT_HEIGHTS {
+                    heights.remove(0);
+                }
+                heights.push(ChunkColumns::new(wpos.z));
+                heights.len() - 1
+            }
+        };
+        heights[i].cells[column_index(wpos)] = Some(cell);
+    }
+
+    /// Column at `wpos`, scanning it only if it isn't cached yet
+    pub fn get(&mut self, terrain: &TerrainGrid, wpos: Vec3<i32>) -> Cell {
+        match self.lookup(wpos) {
+            Some(cell) => cell,
+            None => {
+                let cell = render::scan_column(terrain, wpos);
+                self.insert(wpos, cell);
+                cell
+            }
+        }
+    }
+
+    /// Cells for rows of `width` world positions, with the rows scanned in parallel
+    ///
+    /// Gives exactly the same cells as going through `get` one position at a time. Columns
+    /// missing from the cache are scanned by whichever row needs them and cached afterwards.
+    pub fn scan_rows(
+        &mut self,
+        terrain: &TerrainGrid,
+        wposes: &[Vec3<i32>],
+        width: usize,
+        zoom: f32,
+    ) -> Vec<Cell> {
+        let cache = &*self;
+        let rows: Vec<(Vec<Cell>, Vec<(Vec3<i32>, Cell)>)> = wposes
+            .par_chunks(width.max(1))
+            .map(|row| {
+                let mut scanned = Vec::new();
+                let cells = row
+                    .iter()
+                    .map(|wpos| {
+                        render::scan_cell(*wpos, zoom, |wpos| {
+                            cache.lookup(wpos).unwrap_or_else(|| {
+                                let cell = render::scan_column(terrain, wpos);
+                                scanned.push((wpos, cell));
+                                cell
+                            })
+                        })
+                    })
+                    .collect();
+                (cells, scanned)
+            })
+            .collect();
+
+        let mut cells = Vec::with_capacity(wposes.len());
+        for (row, scanned) in rows {
+            cells.extend(row);
+            for (wpos, cell) in scanned {
+                self.insert(wpos, cell);
+            }
+        }
+        cells
+    }
+}
+
+/// Index of the column at `wpos` within its chunk
+pub fn column_index(wpos: Vec3<i32>) -> usize {
+    let offs = TerrainGrid::chunk_offs(wpos);
+    (offs.y * TerrainChunkSize::RECT_SIZE.x as i32 + offs.x) as usize
+}
+
+/// Time `frames` renders of the cells at `wposes` in rows of `width`: scanning every column
+/// each frame, going through a cache that starts out empty while the height goes up and down
+/// as in a jump, and scanning rows in parallel with a cold cache
+///
+/// Returns the average frame time of each.
+pub fn benchmark(
+    terrain: &TerrainGrid,
+    wposes: &[Vec3<i32>],
+    width: usize,
+    zoom: f32,
+    frames: u32,
+) -> (Duration, Duration, Duration) {
+    let start = Instant::now();
+    for _ in 0..frames {
+        for wpos in wposes {
+            render::scan_cell(*wpos, zoom, |wpos| render::scan_column(terrain, wpos));
+        }
+    }
+    let scan = start.elapsed() / frames;
+
+    let mut cache = HeightmapCache::new();
+    let start = Instant::now();
+    for frame in 0..frames {
+        let up = Vec3::unit_z() * BENCHMARK_HEIGHTS[frame as usize % BENCHMARK_HEIGHTS.len()];
+        for wpos in wposes {
+            render::scan_cell(*wpos + up, zoom, |wpos| cache.get(terrain, wpos));
+        }
+    }
+    let cached = start.elapsed() / frames;
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        HeightmapCache::new().scan_rows(terrain, wposes, width, zoom);
+    }
+    let parallel = start.elapsed() / frames;
+
+    (scan, cached, parallel)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use veloren_common::{
+        terrain::{Block, BlockKind, SpriteKind, TerrainChunk, TerrainChunkMeta},
+        vol::WriteVol,
+    };
+
+    /// Four chunks of flat grass to stand on at height 0, from -32 to 31 along x and y
+    pub fn flat() -> TerrainGrid {
+        let mut terrain = TerrainGrid::new().unwrap();
+        for key in &[
+            Vec2::new(-1, -1),
+            Vec2::new(0, -1),
+            Vec2::new(-1, 0),
+            Vec2::new(0, 0),
+        ] {
+            let chunk = TerrainChunk::new(
+                0,
+                Block::new(BlockKind::Grass, Rgb::new(40, 160, 40)),
+                Block::air(SpriteKind::Empty),
+                TerrainChunkMeta::void(),
+            );
+            terrain.insert(*key, Arc::new(chunk));
+        }
+        terrain
+    }
+
+    /// `flat` with rock pillars of different heights and a few apples about
+    pub fn terrain() -> TerrainGrid {
+        let mut terrain = flat();
+        for y in -32..32 {
+            for x in -32..32 {
+                let height = (x * 7 + y * 13).rem_euclid(5);
+                for z in 0..height {
+                    let rock = Block::new(BlockKind::Rock, Rgb::new(100, 100, 100));
+                    terrain.set(Vec3::new(x, y, z), rock).unwrap();
+                }
+                if (x + y).rem_euclid(11) == 0 {
+                    terrain
+                        .set(Vec3::new(x, y, height), Block::air(SpriteKind::Apple))
+                        .unwrap();
+                }
+            }
+        }
+        terrain
+    }
+
+    fn describe(cell: &Cell) -> (char, RGB, u8, bool, Option<BlockKind>, Option<SpriteKind>) {
+        (
+            cell.glyph,
+            cell.col,
+            cell.glow,
+            cell.roofed,
+            cell.kind,
+            cell.sprite,
+        )
+    }
+
+    #[test]
+    fn columns_are_kept_for_several_heights() {
+        let terrain = terrain();
+        let mut cache = HeightmapCache::new();
+        let wpos = Vec3::new(3, 5, 6);
+        for z in 0..KEPT_HEIGHTS as i32 {
+            cache.get(&terrain, wpos + Vec3::unit_z() * z);
+        }
+        for z in 0..KEPT_HEIGHTS as i32 {
+            assert!(cache.lookup(wpos + Vec3::unit_z() * z).is_some(), "z {}", z);
+        }
+
+        // One height too many forgets the oldest
+        cache.get(&terrain, wpos + Vec3::unit_z() * KEPT_HEIGHTS as i32);
+        assert!(cache.lookup(wpos).is_none());
+        assert!(cache.lookup(wpos + Vec3::unit_z()).is_some());
+
+        let mut changes = TerrainChanges::default();
+        changes.modified_chunks.insert(TerrainGrid::chunk_key(wpos));
+        cache.invalidate(&changes);
+        assert!(cache.lookup(wpos + Vec3::unit_z()).is_none());
+    }
+
+    #[test]
+    fn parallel_scan_matches_serial_scan() {
+        let terrain = terrain();
+        let width = 24;
+        for zoom in &[1.0, 2.5] {
+            let wposes: Vec<_> = (0..16)
+                .flat_map(|y| {
+                    (0..width).map(move |x| {
+                        (Vec2::new(x - width / 2, 8 - y).map(|e| e as f32) * *zoom)
+                            .map(|e| e.floor() as i32)
+                    })
+                })
+                .map(|wpos| Vec3::new(wpos.x, wpos.y, 6))
+                .collect();
+
+            let mut serial_cache = HeightmapCache::new();
+            let serial: Vec<_> = wposes
+                .iter()
+                .map(|wpos| {
+                    render::scan_cell(*wpos, *zoom, |wpos| serial_cache.get(&terrain, wpos))
+                })
+                .collect();
+
+            // Cold, then warmed up by the first scan
+            let mut cache = HeightmapCache::new();
+            for _ in 0..2 {
+                let parallel = cache.scan_rows(&terrain, &wposes, width as usize, *zoom);
+                assert_eq!(parallel.len(), serial.len());
+                for (a, b) in parallel.iter().zip(&serial) {
+                    assert_eq!(describe(a), describe(b));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use bracket_terminal::prelude::*;
+
+use veloren_common::comp::{
+    inventory::slot::EquipSlot, item::ItemKind, CharacterAbility, Inventory, SkillSet,
+};
+
+use crate::{
+    hud,
+    keymap::{Action, Keymap},
+};
+
+/// Number of ability slots on the hotbar besides primary and secondary
+pub const ABILITY_SLOTS: usize = 5;
+
+pub struct HotbarSlot {
+    pub key: String,
+    pub name: String,
+    pub energy_cost: f32,
+    /// Whether the skill the ability needs has been unlocked
+    pub unlocked: bool,
+}
+
+/// Readable name of an ability, which is its variant name
+fn ability_name(ability: &CharacterAbility) -> String {
+    format!("{:?}", ability)
+        .split(|c| c == ' ' || c == '{' || c == '(')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Primary, secondary and numbered abilities of the weapon in the main hand
+pub fn slots(
+    inventory: &Inventory,
+    skill_set: Option<&SkillSet>,
+    keymap: &Keymap,
+) -> Vec<HotbarSlot> {
+    let abilities = match inventory
+        .equipped(EquipSlot::ActiveMainhand)
+        .filter(|item| matches!(item.kind(), ItemKind::Tool(_)))
+    {
+        Some(item) => &item.item_config_expect().abilities,
+        None => return Vec::new(),
+    };
+
+    let mut slots = vec![
+        HotbarSlot {
+            key: keymap.label(Action::Primary),
+            name: ability_name(&abilities.primary),
+            energy_cost: abilities.primary.get_energy_cost() as f32,
+            unlocked: true,
+        },
+        HotbarSlot {
+            key: keymap.label(Action::Secondary),
+            name: ability_name(&abilities.secondary),
+            energy_cost: abilities.secondary.get_energy_cost() as f32,
+            unlocked: true,
+        },
+    ];
+    slots.extend(
+        abilities
+            .abilities
+            .iter()
+            .take(ABILITY_SLOTS)
+            .enumerate()
+            .map(|(i, (skill, ability))| HotbarSlot {
+                key: keymap.label(Action::ABILITIES[i]),
+                name: ability_name(ability),
+                energy_cost: ability.get_energy_cost() as f32,
+                unlocked: skill.map_or(true, |skill| {
+                    skill_set.map_or(false, |skill_set| skill_set.skills.contains_key(&skill))
+                }),
+            }),
+    );
+    slots
+}
+
+/// One slot per line with its key, name and energy cost, marked by whether it can be used now
+///
+/// `busy` is whether the character is still in the middle of an attack, until which no other
+/// ability starts. Abilities have no cooldowns of their own, only energy costs.
+pub fn draw(ctx: &mut BTerm, rect: Rect, slots: &[HotbarSlot], energy: f32, busy: bool) {
+    if !hud::draw_panel(ctx, rect, "Abilities") {
+        return;
+    }
+    // Names line up after the longest key, which can be a rebound key with modifiers
+    let key_width = slots
+        .iter()
+        .map(|slot| slot.key.chars().count() as i32)
+        .max()
+        .unwrap_or(1);
+    for (i, slot) in slots.iter().take((rect.height() - 2) as usize).enumerate() {
+        let y = rect.y1 + 1 + i as i32;
+        let (mark, col) = if !slot.unlocked {
+            ('x', RGB::named(GREY))
+        } else if energy < slot.energy_cost {
+            ('-', RGB::named(YELLOW))
+        } else if busy {
+            ('~', RGB::named(ORANGE))
+        } else {
+            ('•', RGB::named(GREEN))
+        };
+        let cost = format!("{:.0}", slot.energy_cost / 10.0);
+        ctx.print_color(
+            rect.x1 + 1,
+            y,
+            RGB::named(PINK),
+            RGB::named(BLACK),
+            &hud::fit(&slot.key, rect.width() - 2),
+        );
+        ctx.print_color(
+            rect.x1 + 2 + key_width,
+            y,
+            col,
+            RGB::named(BLACK),
+            &hud::fit(&slot.name, rect.width() - 7 - key_width - cost.len() as i32),
+        );
+        ctx.print(rect.x2 - 4 - cost.len() as i32, y, &cost);
+        ctx.print_color(rect.x2 - 2, y, col, RGB::named(BLACK), &mark.to_string());
+    }
+}
@@ -0,0 +1,225 @@
+use bracket_terminal::prelude::*;
+use std::time::Duration;
+
+use veloren_common::comp::{Buff, BuffKind};
+
+/// Cut `text` down to at most `width` characters
+pub fn fit(text: &str, width: i32) -> String {
+    text.chars().take(width.max(0) as usize).collect()
+}
+
+/// Frame a panel and put its title in the top border
+///
+/// Returns false if the panel is too small to hold anything.
+pub fn draw_panel(ctx: &mut BTerm, rect: Rect, title: &str) -> bool {
+    if rect.width() < 3 || rect.height() < 3 {
+        return false;
+    }
+    ctx.draw_box(
+        rect.x1,
+        rect.y1,
+        rect.width() - 1,
+        rect.height() - 1,
+        RGB::named(WHITE),
+        RGB::named(BLACK),
+    );
+    ctx.print_color(
+        rect.x1 + 2,
+        rect.y1,
+        RGB::named(PINK),
+        RGB::named(BLACK),
+        &fit(&format!(" {} ", title), rect.width() - 4),
+    );
+    true
+}
+
+/// Print lines with color markup inside a panel, top to bottom
+pub fn draw_lines(ctx: &mut BTerm, rect: Rect, lines: &[String]) {
+    for (i, line) in lines.iter().take((rect.height() - 2) as usize).enumerate() {
+        ctx.printer(
+            rect.x1 + 1,
+            rect.y1 + 1 + i as i32,
+            line,
+            TextAlign::Left,
+            None,
+        );
+    }
+}
+
+pub fn draw_chat(ctx: &mut BTerm, rect: Rect, chat_log: &[String]) {
+    if !draw_panel(ctx, rect, "Chat") {
+        return;
+    }
+    let rows = (rect.height() - 2) as usize;
+    for (i, msg) in chat_log.iter().rev().take(rows).enumerate() {
+        ctx.print(
+            rect.x1 + 1,
+            rect.y2 - 2 - i as i32,
+            &fit(msg, rect.width() - 2),
+        );
+    }
+}
+
+/// Nearby entities, nearest first, as glyph, glyph color, name, distance in blocks and whether
+/// it's the target
+pub fn draw_entities(ctx: &mut BTerm, rect: Rect, entities: &[(char, RGB, String, f32, bool)]) {
+    if !draw_panel(ctx, rect, "Nearby") {
+        return;
+    }
+    for (i, (glyph, col, name, dist, targeted)) in entities
+        .iter()
+        .take((rect.height() - 2) as usize)
+        .enumerate()
+    {
+        let y = rect.y1 + 1 + i as i32;
+        ctx.print_color(rect.x1 + 1, y, *col, RGB::named(BLACK), &glyph.to_string());
+        let dist = format!("{:.0}m", dist);
+        let (fg, bg) = if *targeted {
+            (RGB::named(BLACK), RGB::named(WHITE))
+        } else {
+            (RGB::named(WHITE), RGB::named(BLACK))
+        };
+        ctx.print_color(
+            rect.x1 + 3,
+            y,
+            fg,
+            bg,
+            &fit(name, rect.width() - 5 - dist.len() as i32),
+        );
+        ctx.print(rect.x2 - 1 - dist.len() as i32, y, &dist);
+    }
+}
+
+/// Inventory item names with the row at `cursor` highlighted
+pub fn draw_inventory(ctx: &mut BTerm, rect: Rect, items: &[String], cursor: usize) {
+    if !draw_panel(ctx, rect, "Inventory") {
+        return;
+    }
+    let rows = (rect.height() - 2) as usize;
+    // Scroll so the cursor stays in view
+    let first = (cursor + 1).saturating_sub(rows);
+    for (i, item) in items.iter().enumerate().skip(first).take(rows) {
+        let (fg, bg) = if i == cursor {
+            (RGB::named(BLACK), RGB::named(WHITE))
+        } else {
+            (RGB::named(WHITE), RGB::named(BLACK))
+        };
+        ctx.print_color(
+            rect.x1 + 1,
+            rect.y1 + 1 + (i - first) as i32,
+            fg,
+            bg,
+            &fit(item, rect.width() - 2),
+        );
+    }
+}
+
+/// Labelled horizontal bar with `current/maximum` printed over it
+pub fn draw_bar(
+    ctx: &mut BTerm,
+    x: i32,
+    y: i32,
+    width: i32,
+    label: &str,
+    (current, maximum): (f32, f32),
+    col: RGB,
+) {
+    ctx.print_color(x, y, RGB::named(PINK), RGB::named(BLACK), label);
+    let x = x + label.chars().count() as i32;
+    let width = width - label.chars().count() as i32;
+    if width <= 0 {
+        return;
+    }
+
+    let filled = if maximum > 0.0 {
+        (current / maximum * width as f32).round() as i32
+    } else {
+        0
+    };
+    let text: Vec<char> = format!("{:.0}/{:.0}", current, maximum).chars().collect();
+    let start = (width - text.len() as i32) / 2;
+    let empty = RGB::from_f32(col.r * 0.25, col.g * 0.25, col.b * 0.25);
+    for i in 0..width {
+        let glyph = text
+            .get((i - start) as usize)
+            .filter(|_| i >= start)
+            .copied();
+        ctx.print_color(
+            x + i,
+            y,
+            RGB::named(WHITE),
+            if i < filled { col } else { empty },
+            &glyph.unwrap_or(' ').to_string(),
+        );
+    }
+}
+
+/// Short glyph for a buff or debuff
+pub fn buff_glyph(kind: BuffKind) -> char {
+    match kind {
+        BuffKind::Regeneration | BuffKind::CampfireHeal => '+',
+        BuffKind::Saturation => '♥',
+        BuffKind::Potion => '!',
+        BuffKind::ProtectingWard => '○',
+        BuffKind::Invulnerability => '☼',
+        BuffKind::Bleeding => '♦',
+        BuffKind::Burning => '▲',
+        BuffKind::Cursed => 'Ω',
+        _ if kind.is_buff() => '↑',
+        _ => '↓',
+    }
+}
+
+/// Active buffs and debuffs with how many of each are stacked
+pub fn draw_effects(ctx: &mut BTerm, rect: Rect, effects: &[(&Buff, usize)]) {
+    if !draw_panel(ctx, rect, "Effects") {
+        return;
+    }
+    for (i, (buff, stacks)) in effects
+        .iter()
+        .take((rect.height() - 2) as usize)
+        .enumerate()
+    {
+        let y = rect.y1 + 1 + i as i32;
+        let col = if buff.kind.is_buff() {
+            RGB::named(GREEN)
+        } else {
+            RGB::named(RED)
+        };
+        ctx.print_color(
+            rect.x1 + 1,
+            y,
+            col,
+            RGB::named(BLACK),
+            &buff_glyph(buff.kind).to_string(),
+        );
+
+        // Stack count and the time left, or nothing for buffs that last until removed
+        let mut info = String::new();
+        if *stacks > 1 {
+            info.push_str(&format!("x{} ", stacks));
+        }
+        if let Some(time) = buff.time {
+            info.push_str(&format_duration(time));
+        }
+        ctx.print(
+            rect.x1 + 3,
+            y,
+            &fit(
+                &format!("{:?}", buff.kind),
+                rect.width() - 5 - info.len() as i32,
+            ),
+        );
+        ctx.print(rect.x2 - 1 - info.len() as i32, y, &info);
+    }
+}
+
+/// Compact countdown like `45s` or `3m05s`
+pub fn format_duration(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
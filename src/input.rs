@@ -0,0 +1,52 @@
+use bracket_terminal::prelude::*;
+use std::collections::HashSet;
+
+/// Which keys are currently held down, kept up to date from bracket-terminal's input events
+#[derive(Default)]
+pub struct HeldKeys {
+    keys: HashSet<VirtualKeyCode>,
+    /// Mouse buttons clicked since the last update, 0 is the left button and 1 the right one
+    clicks: Vec<usize>,
+    /// Text typed since the last update
+    typed: String,
+}
+
+impl HeldKeys {
+    /// Catch up on keys pressed and released since the last tick
+    pub fn update(&mut self) {
+        let keys = &mut self.keys;
+        let clicks = &mut self.clicks;
+        let typed = &mut self.typed;
+        clicks.clear();
+        typed.clear();
+        INPUT.lock().for_each_message(|event| match event {
+            BEvent::KeyboardInput { key, pressed, .. } => {
+                if pressed {
+                    keys.insert(key);
+                } else {
+                    keys.remove(&key);
+                }
+            }
+            BEvent::MouseClick {
+                button,
+                pressed: true,
+            } => clicks.push(button),
+            BEvent::Character { c } if !c.is_control() => typed.push(c),
+            // Releases are missed while the window isn't focused
+            BEvent::Focused { focused: false } => keys.clear(),
+            _ => {}
+        });
+    }
+
+    pub fn is_held(&self, key: VirtualKeyCode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn clicked(&self, button: usize) -> bool {
+        self.clicks.contains(&button)
+    }
+
+    pub fn typed(&self) -> &str {
+        &self.typed
+    }
+}
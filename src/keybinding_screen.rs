@@ -0,0 +1,109 @@
+use bracket_terminal::prelude::*;
+
+use crate::{
+    hud,
+    keymap::{Action, KeyBinding, Keymap},
+};
+
+/// What the keybinding screen wants done after a key press
+pub enum ScreenEvent {
+    None,
+    /// The keymap was changed and should be saved
+    Changed,
+    Close,
+}
+
+/// List of actions and their keys, where keys can be added or cleared
+#[derive(Default)]
+pub struct KeybindingScreen {
+    cursor: usize,
+    /// Waiting for the key to bind to the selected action
+    capturing: bool,
+}
+
+impl KeybindingScreen {
+    pub fn handle_key(&mut self, binding: KeyBinding, keymap: &mut Keymap) -> ScreenEvent {
+        let action = Action::ALL[self.cursor];
+        if self.capturing {
+            // Modifiers are part of the next key, and keys that can't be written to the
+            // settings file can't be bound
+            if binding.is_modifier() || !binding.can_save() {
+                return ScreenEvent::None;
+            }
+            self.capturing = false;
+            if binding.key == VirtualKeyCode::Escape {
+                return ScreenEvent::None;
+            }
+            keymap.add(action, binding);
+            return ScreenEvent::Changed;
+        }
+
+        match binding.key {
+            VirtualKeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            VirtualKeyCode::Down => self.cursor = (self.cursor + 1).min(Action::ALL.len() - 1),
+            VirtualKeyCode::Return => self.capturing = true,
+            VirtualKeyCode::Delete | VirtualKeyCode::Back => {
+                keymap.clear(action);
+                return ScreenEvent::Changed;
+            }
+            VirtualKeyCode::Escape => return ScreenEvent::Close,
+            _ => {}
+        }
+        ScreenEvent::None
+    }
+
+    pub fn draw(&self, ctx: &mut BTerm, rect: Rect, keymap: &Keymap) {
+        if !hud::draw_panel(ctx, rect, "Keybindings") {
+            return;
+        }
+
+        let rows = (rect.height() - 4).max(0) as usize;
+        let first = (self.cursor + 1).saturating_sub(rows);
+        for (i, action) in Action::ALL.iter().enumerate().skip(first).take(rows) {
+            let keys = if self.capturing && i == self.cursor {
+                "press a key...".to_string()
+            } else {
+                keymap
+                    .bindings(*action)
+                    .iter()
+                    .map(|binding| binding.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let conflicts = keymap.conflicts(*action);
+            let mut line = format!("{:<18}{}", format!("{:?}", action), keys);
+            if !conflicts.is_empty() {
+                line.push_str(&format!("  (also {:?})", conflicts));
+            }
+
+            let fg = if conflicts.is_empty() {
+                RGB::named(WHITE)
+            } else {
+                RGB::named(RED)
+            };
+            let (fg, bg) = if i == self.cursor {
+                (RGB::named(BLACK), fg)
+            } else {
+                (fg, RGB::named(BLACK))
+            };
+            ctx.print_color(
+                rect.x1 + 2,
+                rect.y1 + 1 + (i - first) as i32,
+                fg,
+                bg,
+                &hud::fit(&line, rect.width() - 4),
+            );
+        }
+
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y2 - 2,
+            RGB::named(GREY),
+            RGB::named(BLACK),
+            &hud::fit(
+                "Up/Down - Select  Enter - Add key  Delete - Clear keys  Esc - Close",
+                rect.width() - 4,
+            ),
+        );
+    }
+}
@@ -0,0 +1,720 @@
+use bracket_terminal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+use vek::*;
+
+use crate::input::HeldKeys;
+
+/// Everything a key can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    Jump,
+    Primary,
+    Secondary,
+    Ability1,
+    Ability2,
+    Ability3,
+    Ability4,
+    Ability5,
+    Glide,
+    Lantern,
+    Respawn,
+    FindResource,
+    Explore,
+    ZoomIn,
+    ZoomOut,
+    AcceptInvite,
+    DeclineInvite,
+    ToggleInventory,
+    InventoryUp,
+    InventoryDown,
+    InventorySelect,
+    InventoryUse,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    MenuSelect,
+    CharacterSheet,
+    WorldMap,
+    AddWaypoint,
+    Waypoints,
+    WaypointTravel,
+    WaypointRename,
+    WaypointShare,
+    WaypointRemove,
+    Keybindings,
+    Benchmark,
+}
+
+impl Action {
+    /// Every action, in the order the keybinding screen lists them
+    pub const ALL: [Action; 45] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUpLeft,
+        Action::MoveUpRight,
+        Action::MoveDownLeft,
+        Action::MoveDownRight,
+        Action::Jump,
+        Action::Primary,
+        Action::Secondary,
+        Action::Ability1,
+        Action::Ability2,
+        Action::Ability3,
+        Action::Ability4,
+        Action::Ability5,
+        Action::Glide,
+        Action::Lantern,
+        Action::Respawn,
+        Action::FindResource,
+        Action::Explore,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::AcceptInvite,
+        Action::DeclineInvite,
+        Action::ToggleInventory,
+        Action::InventoryUp,
+        Action::InventoryDown,
+        Action::InventorySelect,
+        Action::InventoryUse,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLeft,
+        Action::MenuRight,
+        Action::MenuSelect,
+        Action::CharacterSheet,
+        Action::WorldMap,
+        Action::AddWaypoint,
+        Action::Waypoints,
+        Action::WaypointTravel,
+        Action::WaypointRename,
+        Action::WaypointShare,
+        Action::WaypointRemove,
+        Action::Keybindings,
+        Action::Benchmark,
+    ];
+
+    /// Numbered ability actions, `Ability1` first
+    pub const ABILITIES: [Action; 5] = [
+        Action::Ability1,
+        Action::Ability2,
+        Action::Ability3,
+        Action::Ability4,
+        Action::Ability5,
+    ];
+
+    /// Whether the action moves around the character sheet and other full-screen menus
+    pub fn is_menu(self) -> bool {
+        self.context() == Context::Menu
+    }
+
+    fn context(self) -> Context {
+        match self {
+            Action::InventoryUp
+            | Action::InventoryDown
+            | Action::InventorySelect
+            | Action::InventoryUse => Context::Inventory,
+            Action::MenuUp
+            | Action::MenuDown
+            | Action::MenuLeft
+            | Action::MenuRight
+            | Action::MenuSelect => Context::Menu,
+            Action::WaypointTravel
+            | Action::WaypointRename
+            | Action::WaypointShare
+            | Action::WaypointRemove => Context::WaypointList,
+            _ => Context::Game,
+        }
+    }
+
+    /// Whether sharing a key with `other` would make the key do two things at once
+    ///
+    /// Open windows take their keys from movement, so only other game actions clash with them.
+    fn can_clash(self, other: Action) -> bool {
+        match (self.context(), other.context()) {
+            (a, b) if a == b => true,
+            // The waypoint list takes every key, and moves its cursor with the menu keys
+            (Context::WaypointList, context) | (context, Context::WaypointList) => {
+                context == Context::Menu
+            }
+            (Context::Game, _) => self.move_dir().is_none(),
+            (_, Context::Game) => other.move_dir().is_none(),
+            _ => false,
+        }
+    }
+
+    /// Direction a movement action pushes towards
+    fn move_dir(self) -> Option<Vec2<f32>> {
+        match self {
+            Action::MoveUp => Some(Vec2::new(0.0, 1.0)),
+            Action::MoveDown => Some(Vec2::new(0.0, -1.0)),
+            Action::MoveLeft => Some(Vec2::new(-1.0, 0.0)),
+            Action::MoveRight => Some(Vec2::new(1.0, 0.0)),
+            Action::MoveUpLeft => Some(Vec2::new(-1.0, 1.0)),
+            Action::MoveUpRight => Some(Vec2::new(1.0, 1.0)),
+            Action::MoveDownLeft => Some(Vec2::new(-1.0, -1.0)),
+            Action::MoveDownRight => Some(Vec2::new(1.0, -1.0)),
+            _ => None,
+        }
+    }
+}
+
+/// When the keys of an action are read
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Context {
+    /// While playing
+    Game,
+    /// While the inventory is open
+    Inventory,
+    /// While the character sheet or waypoint list is open, which cover the inventory
+    Menu,
+    /// While the waypoint list is open, which takes all keys
+    WaypointList,
+}
+
+/// Built-in sets of movement keys, any number of which can be used at once
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MovementPreset {
+    /// `8462` and `7913` for diagonals, `5` also moves down
+    Numpad,
+    Wasd,
+    /// `hjkl` and `yubn` for diagonals, like NetHack
+    ViKeys,
+    Arrows,
+}
+
+impl MovementPreset {
+    /// Keys of the preset that trigger a movement action
+    fn keys(self, action: Action) -> &'static [VirtualKeyCode] {
+        use VirtualKeyCode::*;
+        match (self, action) {
+            (MovementPreset::Numpad, Action::MoveUp) => &[Numpad8],
+            (MovementPreset::Numpad, Action::MoveDown) => &[Numpad2, Numpad5],
+            (MovementPreset::Numpad, Action::MoveLeft) => &[Numpad4],
+            (MovementPreset::Numpad, Action::MoveRight) => &[Numpad6],
+            (MovementPreset::Numpad, Action::MoveUpLeft) => &[Numpad7],
+            (MovementPreset::Numpad, Action::MoveUpRight) => &[Numpad9],
+            (MovementPreset::Numpad, Action::MoveDownLeft) => &[Numpad1],
+            (MovementPreset::Numpad, Action::MoveDownRight) => &[Numpad3],
+            (MovementPreset::Wasd, Action::MoveUp) => &[W],
+            (MovementPreset::Wasd, Action::MoveDown) => &[S],
+            (MovementPreset::Wasd, Action::MoveLeft) => &[A],
+            (MovementPreset::Wasd, Action::MoveRight) => &[D],
+            (MovementPreset::ViKeys, Action::MoveUp) => &[K],
+            (MovementPreset::ViKeys, Action::MoveDown) => &[J],
+            (MovementPreset::ViKeys, Action::MoveLeft) => &[H],
+            (MovementPreset::ViKeys, Action::MoveRight) => &[L],
+            (MovementPreset::ViKeys, Action::MoveUpLeft) => &[Y],
+            (MovementPreset::ViKeys, Action::MoveUpRight) => &[U],
+            (MovementPreset::ViKeys, Action::MoveDownLeft) => &[B],
+            (MovementPreset::ViKeys, Action::MoveDownRight) => &[N],
+            (MovementPreset::Arrows, Action::MoveUp) => &[Up],
+            (MovementPreset::Arrows, Action::MoveDown) => &[Down],
+            (MovementPreset::Arrows, Action::MoveLeft) => &[Left],
+            (MovementPreset::Arrows, Action::MoveRight) => &[Right],
+            _ => &[],
+        }
+    }
+}
+
+/// Keys that can be named in the settings file, by their `VirtualKeyCode` variant name
+const KEYS: &[VirtualKeyCode] = &[
+    VirtualKeyCode::A,
+    VirtualKeyCode::B,
+    VirtualKeyCode::C,
+    VirtualKeyCode::D,
+    VirtualKeyCode::E,
+    VirtualKeyCode::F,
+    VirtualKeyCode::G,
+    VirtualKeyCode::H,
+    VirtualKeyCode::I,
+    VirtualKeyCode::J,
+    VirtualKeyCode::K,
+    VirtualKeyCode::L,
+    VirtualKeyCode::M,
+    VirtualKeyCode::N,
+    VirtualKeyCode::O,
+    VirtualKeyCode::P,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::R,
+    VirtualKeyCode::S,
+    VirtualKeyCode::T,
+    VirtualKeyCode::U,
+    VirtualKeyCode::V,
+    VirtualKeyCode::W,
+    VirtualKeyCode::X,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::Key0,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+    VirtualKeyCode::Numpad0,
+    VirtualKeyCode::Numpad1,
+    VirtualKeyCode::Numpad2,
+    VirtualKeyCode::Numpad3,
+    VirtualKeyCode::Numpad4,
+    VirtualKeyCode::Numpad5,
+    VirtualKeyCode::Numpad6,
+    VirtualKeyCode::Numpad7,
+    VirtualKeyCode::Numpad8,
+    VirtualKeyCode::Numpad9,
+    VirtualKeyCode::F1,
+    VirtualKeyCode::F2,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+    VirtualKeyCode::F5,
+    VirtualKeyCode::F6,
+    VirtualKeyCode::F7,
+    VirtualKeyCode::F8,
+    VirtualKeyCode::F9,
+    VirtualKeyCode::F10,
+    VirtualKeyCode::F11,
+    VirtualKeyCode::F12,
+    VirtualKeyCode::Up,
+    VirtualKeyCode::Down,
+    VirtualKeyCode::Left,
+    VirtualKeyCode::Right,
+    VirtualKeyCode::Space,
+    VirtualKeyCode::Return,
+    VirtualKeyCode::Back,
+    VirtualKeyCode::Tab,
+    VirtualKeyCode::Escape,
+    VirtualKeyCode::Delete,
+    VirtualKeyCode::Insert,
+    VirtualKeyCode::Home,
+    VirtualKeyCode::End,
+    VirtualKeyCode::PageUp,
+    VirtualKeyCode::PageDown,
+    VirtualKeyCode::Plus,
+    VirtualKeyCode::Minus,
+    VirtualKeyCode::Equals,
+    VirtualKeyCode::Comma,
+    VirtualKeyCode::Period,
+    VirtualKeyCode::Slash,
+    VirtualKeyCode::Backslash,
+    VirtualKeyCode::Semicolon,
+    VirtualKeyCode::Apostrophe,
+    VirtualKeyCode::LBracket,
+    VirtualKeyCode::RBracket,
+    VirtualKeyCode::Grave,
+];
+
+/// A key together with the modifiers that have to be held with it
+///
+/// Written in the settings file as e.g. `"W"` or `"Ctrl+Shift+F1"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    pub key: VirtualKeyCode,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key: VirtualKeyCode, shift: bool, ctrl: bool, alt: bool) -> Self {
+        Self {
+            key,
+            shift,
+            ctrl,
+            alt,
+        }
+    }
+
+    fn key(key: VirtualKeyCode) -> Self {
+        Self::new(key, false, false, false)
+    }
+
+    /// Whether the key is a modifier, which is only held together with other keys
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.key,
+            VirtualKeyCode::LShift
+                | VirtualKeyCode::RShift
+                | VirtualKeyCode::LControl
+                | VirtualKeyCode::RControl
+                | VirtualKeyCode::LAlt
+                | VirtualKeyCode::RAlt
+                | VirtualKeyCode::LWin
+                | VirtualKeyCode::RWin
+        )
+    }
+
+    /// Whether the binding reads back the same from the settings file
+    pub fn can_save(&self) -> bool {
+        KeyBinding::try_from(self.to_string()) == Ok(*self)
+    }
+
+    /// `key` with the modifiers held right now
+    fn held_with(key: VirtualKeyCode, held: &HeldKeys) -> Self {
+        Self::new(
+            key,
+            held.is_held(VirtualKeyCode::LShift) || held.is_held(VirtualKeyCode::RShift),
+            held.is_held(VirtualKeyCode::LControl) || held.is_held(VirtualKeyCode::RControl),
+            held.is_held(VirtualKeyCode::LAlt) || held.is_held(VirtualKeyCode::RAlt),
+        )
+    }
+
+    /// Whether the key is held with exactly its modifiers, the same way a pressed key only
+    /// triggers the actions bound to it with the modifiers pressed
+    pub fn is_held(&self, held: &HeldKeys) -> bool {
+        held.is_held(self.key) && Self::held_with(self.key, held) == *self
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(binding: KeyBinding) -> String {
+        binding.to_string()
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let mut binding = KeyBinding::key(VirtualKeyCode::Escape);
+        let mut parts: Vec<&str> = s.split('+').map(|part| part.trim()).collect();
+        let key = parts.pop().unwrap_or_default();
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "shift" => binding.shift = true,
+                "ctrl" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                _ => return Err(format!("unknown modifier `{}` in `{}`", modifier, s)),
+            }
+        }
+        binding.key = *KEYS
+            .iter()
+            .find(|k| format!("{:?}", k).eq_ignore_ascii_case(key))
+            .ok_or_else(|| format!("unknown key `{}`", key))?;
+        Ok(binding)
+    }
+}
+
+/// Keys bound to each action, any of which triggers it
+///
+/// Keys of the movement presets come on top of the bound keys and aren't saved with them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<Action, Vec<KeyBinding>>",
+    into = "BTreeMap<Action, Vec<KeyBinding>>"
+)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<KeyBinding>>,
+    movement: Vec<MovementPreset>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let bindings = vec![
+            (Action::Jump, vec![Space]),
+            (Action::Primary, vec![X]),
+            (Action::Secondary, vec![Z]),
+            (Action::Ability1, vec![Key1]),
+            (Action::Ability2, vec![Key2]),
+            (Action::Ability3, vec![Key3]),
+            (Action::Ability4, vec![Key4]),
+            (Action::Ability5, vec![Key5]),
+            (Action::Glide, vec![G]),
+            (Action::Lantern, vec![L]),
+            (Action::Respawn, vec![R]),
+            (Action::FindResource, vec![F]),
+            (Action::Explore, vec![O]),
+            (Action::ZoomIn, vec![Plus]),
+            (Action::ZoomOut, vec![Minus]),
+            (Action::AcceptInvite, vec![U]),
+            (Action::DeclineInvite, vec![I]),
+            (Action::ToggleInventory, vec![T]),
+            (Action::InventoryUp, vec![Up]),
+            (Action::InventoryDown, vec![Down]),
+            (Action::InventorySelect, vec![Right]),
+            (Action::InventoryUse, vec![Left]),
+            (Action::MenuUp, vec![Up]),
+            (Action::MenuDown, vec![Down]),
+            (Action::MenuLeft, vec![Left]),
+            (Action::MenuRight, vec![Right]),
+            (Action::MenuSelect, vec![Return]),
+            (Action::CharacterSheet, vec![K]),
+            (Action::WorldMap, vec![M]),
+            (Action::AddWaypoint, vec![N]),
+            (Action::Waypoints, vec![P]),
+            (Action::WaypointTravel, vec![G]),
+            (Action::WaypointRename, vec![R]),
+            (Action::WaypointShare, vec![S]),
+            (Action::WaypointRemove, vec![Delete, Back]),
+            (Action::Keybindings, vec![F1]),
+            (Action::Benchmark, vec![F12]),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(action, keys)| (action, keys.into_iter().map(KeyBinding::key).collect()))
+                .collect(),
+            movement: Vec::new(),
+        }
+    }
+}
+
+/// Actions missing from the settings file keep their default keys
+impl From<BTreeMap<Action, Vec<KeyBinding>>> for Keymap {
+    fn from(bindings: BTreeMap<Action, Vec<KeyBinding>>) -> Self {
+        let mut keymap = Keymap::default();
+        keymap.bindings.extend(bindings);
+        keymap
+    }
+}
+
+impl From<Keymap> for BTreeMap<Action, Vec<KeyBinding>> {
+    fn from(keymap: Keymap) -> Self {
+        keymap.bindings
+    }
+}
+
+impl Keymap {
+    /// Use the keys of `movement` on top of the bound ones
+    ///
+    /// Movement comes first, so other actions bound to a preset's key move to that key with
+    /// Shift held, such as the character sheet to `Shift+K` with `ViKeys`. Only `bindings` sees
+    /// them moved, the saved keys stay as they were bound.
+    pub fn set_movement(&mut self, movement: &[MovementPreset]) {
+        self.movement = movement.to_vec();
+    }
+
+    /// `binding` of `action`, with Shift added if a movement preset has taken the key
+    fn shifted(&self, action: Action, binding: KeyBinding) -> KeyBinding {
+        let taken = action.move_dir().is_none()
+            && binding == KeyBinding::key(binding.key)
+            && Action::ALL.iter().any(|other| {
+                action.can_clash(*other)
+                    && self
+                        .movement
+                        .iter()
+                        .any(|preset| preset.keys(*other).contains(&binding.key))
+            });
+        KeyBinding {
+            shift: binding.shift || taken,
+            ..binding
+        }
+    }
+
+    /// Bound keys of the action followed by the keys the movement presets add
+    pub fn bindings(&self, action: Action) -> Vec<KeyBinding> {
+        let bound = self.bindings.get(&action).into_iter().flatten();
+        let preset = self
+            .movement
+            .iter()
+            .flat_map(|preset| preset.keys(action))
+            .map(|key| KeyBinding::key(*key));
+        let mut keys = Vec::new();
+        for binding in bound
+            .map(|binding| self.shifted(action, *binding))
+            .chain(preset)
+        {
+            if !keys.contains(&binding) {
+                keys.push(binding);
+            }
+        }
+        keys
+    }
+
+    pub fn add(&mut self, action: Action, binding: KeyBinding) {
+        let keys = self.bindings.entry(action).or_default();
+        if !keys.contains(&binding) {
+            keys.push(binding);
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.insert(action, Vec::new());
+    }
+
+    /// Actions bound to exactly this key and modifiers
+    pub fn actions(&self, binding: KeyBinding) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(|action| self.bindings(*action).contains(&binding))
+            .collect()
+    }
+
+    /// First key of the action, or nothing if it's unbound
+    pub fn label(&self, action: Action) -> String {
+        self.bindings(action)
+            .first()
+            .map_or_else(String::new, |binding| binding.to_string())
+    }
+
+    /// Other actions sharing one of this action's keys while both are read
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let mut conflicts: Vec<_> = self
+            .bindings(action)
+            .into_iter()
+            .flat_map(|binding| self.actions(binding))
+            .filter(|other| *other != action && action.can_clash(*other))
+            .collect();
+        conflicts.sort();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Whether any key of the action is held down
+    pub fn is_held(&self, action: Action, held: &HeldKeys) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.is_held(held))
+    }
+
+    /// Direction the held movement keys point in, no longer than 1 so diagonals aren't faster
+    ///
+    /// Keys in `reserved` are taken by an open window and don't move.
+    pub fn move_dir(&self, held: &HeldKeys, reserved: &[KeyBinding]) -> Vec2<f32> {
+        let dir = Action::ALL
+            .iter()
+            .filter(|action| {
+                self.bindings(**action)
+                    .iter()
+                    .any(|binding| !reserved.contains(binding) && binding.is_held(held))
+            })
+            .filter_map(|action| action.move_dir())
+            .fold(Vec2::zero(), |sum, dir| sum + dir);
+        if dir.magnitude_squared() > 1.0 {
+            dir.normalized()
+        } else {
+            dir
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_round_trips_through_its_name() {
+        let binding = KeyBinding::new(VirtualKeyCode::F1, true, true, false);
+        assert_eq!(binding.to_string(), "Ctrl+Shift+F1");
+        assert_eq!(KeyBinding::try_from(binding.to_string()), Ok(binding));
+        assert!(binding.can_save());
+    }
+
+    #[test]
+    fn binding_names_ignore_case_and_spaces() {
+        assert_eq!(
+            KeyBinding::try_from("alt + w".to_string()),
+            Ok(KeyBinding::new(VirtualKeyCode::W, false, false, true))
+        );
+    }
+
+    #[test]
+    fn unknown_keys_and_modifiers_fail_to_parse() {
+        assert!(KeyBinding::try_from("NumpadEnter".to_string()).is_err());
+        assert!(KeyBinding::try_from("Meta+W".to_string()).is_err());
+        assert!(KeyBinding::try_from(String::new()).is_err());
+    }
+
+    #[test]
+    fn keys_only_trigger_with_exactly_their_modifiers() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.actions(KeyBinding::key(VirtualKeyCode::X)),
+            vec![Action::Primary]
+        );
+        assert!(keymap
+            .actions(KeyBinding::new(VirtualKeyCode::X, false, true, false))
+            .is_empty());
+    }
+
+    #[test]
+    fn movement_presets_move_other_actions_to_shift() {
+        let mut keymap = Keymap::default();
+        keymap.set_movement(&[MovementPreset::ViKeys]);
+        assert_eq!(
+            keymap.bindings(Action::CharacterSheet),
+            vec![KeyBinding::new(VirtualKeyCode::K, true, false, false)]
+        );
+        assert_eq!(
+            keymap.bindings(Action::Lantern),
+            vec![KeyBinding::new(VirtualKeyCode::L, true, false, false)]
+        );
+        for action in Action::ALL.iter() {
+            assert!(keymap.conflicts(*action).is_empty(), "{:?} clashes", action);
+        }
+    }
+
+    #[test]
+    fn keys_moved_to_shift_are_saved_unshifted() {
+        let mut keymap = Keymap::default();
+        keymap.set_movement(&[MovementPreset::ViKeys]);
+        keymap.add(Action::Glide, KeyBinding::key(VirtualKeyCode::F2));
+
+        let saved = ron::ser::to_string(&keymap).unwrap();
+        let mut reloaded: Keymap = ron::de::from_str(&saved).unwrap();
+        reloaded.set_movement(&[MovementPreset::Wasd]);
+        assert_eq!(
+            reloaded.bindings(Action::CharacterSheet),
+            vec![KeyBinding::key(VirtualKeyCode::K)]
+        );
+        assert_eq!(
+            reloaded.bindings(Action::Lantern),
+            vec![KeyBinding::key(VirtualKeyCode::L)]
+        );
+        assert_eq!(
+            reloaded.bindings(Action::Glide),
+            vec![
+                KeyBinding::key(VirtualKeyCode::G),
+                KeyBinding::key(VirtualKeyCode::F2)
+            ]
+        );
+    }
+
+    #[test]
+    fn window_keys_only_clash_with_game_actions_that_dont_move() {
+        let mut keymap = Keymap::default();
+        keymap.set_movement(&[MovementPreset::Arrows]);
+        assert!(keymap.conflicts(Action::InventoryUp).is_empty());
+        keymap.add(Action::Jump, KeyBinding::key(VirtualKeyCode::Up));
+        assert_eq!(keymap.conflicts(Action::InventoryUp), vec![Action::Jump]);
+    }
+
+    #[test]
+    fn only_named_keys_can_be_saved() {
+        assert!(!KeyBinding::key(VirtualKeyCode::NumpadEnter).can_save());
+        assert!(!KeyBinding::key(VirtualKeyCode::Capital).can_save());
+        assert!(KeyBinding::key(VirtualKeyCode::LShift).is_modifier());
+        assert!(!KeyBinding::key(VirtualKeyCode::W).is_modifier());
+    }
+}
@@ -0,0 +1,201 @@
+use bracket_terminal::prelude::Rect;
+use serde::{Deserialize, Serialize};
+use vek::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanelKind {
+    Map,
+    Chat,
+    Status,
+    Effects,
+    Hotbar,
+    Minimap,
+    Compass,
+    Entities,
+    Inventory,
+}
+
+/// A panel docked to the screen edges
+///
+/// Negative `x`/`y` count from the right/bottom edge. A `width`/`height` of 0 or less stretches
+/// the panel to that many cells short of the right/bottom edge, so panels keep their place as
+/// the window is resized. All values are in map console cells.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Panel {
+    pub kind: PanelKind,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Panel {
+    fn new(kind: PanelKind, x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            kind,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Where the panel ends up on a screen of the given size
+    fn resolve(&self, size: Vec2<i32>) -> Rect {
+        let x = if self.x < 0 { size.x + self.x } else { self.x };
+        let y = if self.y < 0 { size.y + self.y } else { self.y };
+        let width = if self.width <= 0 {
+            size.x + self.width - x
+        } else {
+            self.width
+        };
+        let height = if self.height <= 0 {
+            size.y + self.height - y
+        } else {
+            self.height
+        };
+        let x = x.clamp(0, size.x);
+        let y = y.clamp(0, size.y);
+        Rect::with_size(
+            x,
+            y,
+            width.clamp(0, size.x - x),
+            height.clamp(0, size.y - y),
+        )
+    }
+}
+
+/// Panel arrangement chosen in the settings file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LayoutPreset {
+    /// Map on the left, status, effects, abilities, entities and inventory down the right, chat
+    /// under the map and the compass and minimap next to it, covered by the inventory while it
+    /// is open
+    DfClassic,
+    /// Like `DfClassic`, but with chat across the whole bottom of the screen, the minimap and
+    /// compass in the corner of the map and the inventory covering the entity list while it is
+    /// open
+    WideChat,
+    /// Nothing but the map
+    MapOnly,
+    Custom(Vec<Panel>),
+}
+
+impl Default for LayoutPreset {
+    fn default() -> Self {
+        LayoutPreset::DfClassic
+    }
+}
+
+impl LayoutPreset {
+    pub fn panels(&self) -> Vec<Panel> {
+        use PanelKind::*;
+        match self {
+            LayoutPreset::DfClassic => vec![
+                Panel::new(Map, 0, 0, -24, -12),
+                Panel::new(Chat, 0, -12, -24, 0),
+                Panel::new(Status, -24, 0, 24, 9),
+                Panel::new(Effects, -24, 9, 24, 6),
+                Panel::new(Hotbar, -24, 15, 24, 9),
+                Panel::new(Entities, -24, 24, 24, 7),
+                Panel::new(Compass, -24, -19, 24, 7),
+                Panel::new(Minimap, -24, -12, 24, 12),
+                Panel::new(Inventory, -24, 31, 24, 0),
+            ],
+            LayoutPreset::WideChat => vec![
+                Panel::new(Map, 0, 0, -24, -16),
+                Panel::new(Chat, 0, -16, 0, 0),
+                Panel::new(Minimap, -48, 0, 24, 12),
+                Panel::new(Compass, -48, 12, 24, 7),
+                Panel::new(Status, -24, 0, 24, 9),
+                Panel::new(Effects, -24, 9, 24, 6),
+                Panel::new(Hotbar, -24, 15, 24, 9),
+                Panel::new(Entities, -24, 24, 24, -16),
+                Panel::new(Inventory, -24, 24, 24, -16),
+            ],
+            LayoutPreset::MapOnly => vec![Panel::new(Map, 0, 0, 0, 0)],
+            LayoutPreset::Custom(panels) => panels.clone(),
+        }
+    }
+}
+
+/// Panels resolved against the current console size
+pub struct Layout {
+    size: Vec2<i32>,
+    rects: Vec<(PanelKind, Rect)>,
+}
+
+impl Layout {
+    pub fn new(preset: &LayoutPreset, size: Vec2<i32>) -> Self {
+        Self {
+            size,
+            rects: preset
+                .panels()
+                .iter()
+                .map(|panel| (panel.kind, panel.resolve(size)))
+                .collect(),
+        }
+    }
+
+    /// Where a panel is in map console cells, if the layout has it
+    pub fn get(&self, kind: PanelKind) -> Option<Rect> {
+        self.rects
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, rect)| *rect)
+    }
+
+    /// Where a panel is on a console of a different size, such as a HUD with a smaller font
+    pub fn get_on(&self, kind: PanelKind, console_size: Vec2<i32>) -> Option<Rect> {
+        self.get(kind).map(|rect| {
+            let scale = |e: i32, from: i32, to: i32| e * to / from.max(1);
+            Rect::with_exact(
+                scale(rect.x1, self.size.x, console_size.x),
+                scale(rect.y1, self.size.y, console_size.y),
+                scale(rect.x2, self.size.x, console_size.x),
+                scale(rect.y2, self.size.y, console_size.y),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panels_dock_to_the_edges() {
+        let size = Vec2::new(80, 50);
+        let map = Panel::new(PanelKind::Map, 0, 0, -24, -12);
+        assert_eq!(map.resolve(size), Rect::with_size(0, 0, 56, 38));
+        let status = Panel::new(PanelKind::Status, -24, 0, 24, 9);
+        assert_eq!(status.resolve(size), Rect::with_size(56, 0, 24, 9));
+        let inventory = Panel::new(PanelKind::Inventory, -24, 31, 24, 0);
+        assert_eq!(inventory.resolve(size), Rect::with_size(56, 31, 24, 19));
+    }
+
+    #[test]
+    fn panels_stay_on_small_screens() {
+        let size = Vec2::new(20, 5);
+        let status = Panel::new(PanelKind::Status, -24, 0, 24, 9);
+        assert_eq!(status.resolve(size), Rect::with_size(0, 0, 20, 5));
+        let chat = Panel::new(PanelKind::Chat, 0, 10, 0, 0);
+        assert_eq!(chat.resolve(size), Rect::with_size(0, 5, 20, 0));
+    }
+
+    #[test]
+    fn presets_fit_on_screen() {
+        let size = Vec2::new(80, 50);
+        for preset in &[
+            LayoutPreset::DfClassic,
+            LayoutPreset::WideChat,
+            LayoutPreset::MapOnly,
+        ] {
+            for panel in preset.panels() {
+                let rect = panel.resolve(size);
+                assert!(rect.width() > 0 && rect.height() > 0, "{:?}", panel.kind);
+                assert!(rect.x2 <= size.x && rect.y2 <= size.y, "{:?}", panel.kind);
+            }
+        }
+    }
+}
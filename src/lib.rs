@@ -41,5 +41,31 @@ pub fn read_arguments() -> clap::ArgMatches<'static> {
                 .required(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("settings")
+                .long("settings")
+                .value_name("FILE")
+                .help("Set the settings file to use")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .value_name("COLUMNS")
+                .help("Set the console width in characters")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("height")
+                .long("height")
+                .value_name("ROWS")
+                .help("Set the console height in characters")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("benchmark")
+                .long("benchmark")
+                .help("Time map scanning once the terrain in view has loaded, print it and quit"),
+        )
         .get_matches()
 }
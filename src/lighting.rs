@@ -0,0 +1,144 @@
+use bracket_terminal::prelude::*;
+use vek::*;
+
+/// Length of an in-game day in seconds, as counted by `TimeOfDay`
+const DAY_LENGTH: f64 = 24.0 * 3600.0;
+/// Light level nothing drops below, so dark areas stay faintly readable
+const AMBIENT: f32 = 0.12;
+/// How many blocks a light of `LightEmitter::strength` 1.0 reaches
+const EMITTER_RANGE: f32 = 4.0;
+
+/// How bright the sky is at the given time of day, from 0.0 at midnight to 1.0 around noon
+pub fn daylight(time_of_day: f64) -> f32 {
+    let t = (time_of_day.rem_euclid(DAY_LENGTH) / DAY_LENGTH) as f32;
+    (0.5 - (t * std::f32::consts::TAU).cos() * 0.75).clamp(0.0, 1.0)
+}
+
+/// Block range of a `LightEmitter` with the given strength
+pub fn emitter_range(strength: f32) -> f32 {
+    strength * EMITTER_RANGE
+}
+
+/// Per-cell light levels for the map view
+pub struct LightMap {
+    size: Vec2<i32>,
+    light: Vec<f32>,
+}
+
+impl LightMap {
+    /// Start with sky light on every cell that isn't roofed over
+    pub fn new(size: Vec2<i32>, sky: f32, roofed: impl Iterator<Item = bool>) -> Self {
+        Self {
+            size,
+            light: roofed.map(|r| if r { 0.0 } else { sky }).collect(),
+        }
+    }
+
+    /// Add a light at a screen cell reaching `radius` cells, falling off linearly
+    pub fn add_light(&mut self, pos: Vec2<i32>, radius: f32) {
+        if radius <= 0.0 {
+            return;
+        }
+        let r = radius.ceil() as i32;
+        for y in (pos.y - r).max(0)..(pos.y + r + 1).min(self.size.y) {
+            for x in (pos.x - r).max(0)..(pos.x + r + 1).min(self.size.x) {
+                let dist = Vec2::new(x - pos.x, y - pos.y)
+                    .map(|e| e as f32)
+                    .magnitude();
+                if dist < radius {
+                    let l = &mut self.light[(y * self.size.x + x) as usize];
+                    *l = (*l + 1.0 - dist / radius).min(1.0);
+                }
+            }
+        }
+    }
+
+    /// Light level at a screen cell, or ambient light outside the map
+    pub fn get(&self, pos: Vec2<i32>) -> f32 {
+        if pos.map2(self.size, |e, sz| e >= 0 && e < sz).reduce_and() {
+            self.light[(pos.y * self.size.x + pos.x) as usize].max(AMBIENT)
+        } else {
+            AMBIENT
+        }
+    }
+
+    /// Darken a color by the light level at a screen cell
+    pub fn apply(&self, pos: Vec2<i32>, col: RGB) -> RGB {
+        let l = self.get(pos);
+        RGB::from_f32(col.r * l, col.g * l, col.b * l)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn dark(size: Vec2<i32>) -> LightMap {
+        LightMap::new(
+            size,
+            0.0,
+            std::iter::repeat(false).take((size.x * size.y) as usize),
+        )
+    }
+
+    #[test]
+    fn dark_at_midnight_and_bright_at_noon() {
+        assert_near(daylight(0.0), 0.0);
+        assert_near(daylight(DAY_LENGTH / 2.0), 1.0);
+        assert_near(daylight(DAY_LENGTH * 3.0 + DAY_LENGTH / 2.0), 1.0);
+        assert!(daylight(DAY_LENGTH / 4.0) > 0.0 && daylight(DAY_LENGTH / 4.0) < 1.0);
+    }
+
+    #[test]
+    fn roofed_cells_get_no_sky_light() {
+        let size = Vec2::new(2, 1);
+        let light = LightMap::new(size, 0.8, vec![false, true].into_iter());
+        assert_near(light.get(Vec2::new(0, 0)), 0.8);
+        assert_near(light.get(Vec2::new(1, 0)), AMBIENT);
+    }
+
+    #[test]
+    fn lights_fall_off_linearly() {
+        let mut light = dark(Vec2::new(9, 9));
+        light.add_light(Vec2::new(4, 4), 4.0);
+        assert_near(light.get(Vec2::new(4, 4)), 1.0);
+        assert_near(light.get(Vec2::new(5, 4)), 0.75);
+        assert_near(light.get(Vec2::new(4, 2)), 0.5);
+        assert_near(light.get(Vec2::new(8, 4)), AMBIENT);
+    }
+
+    #[test]
+    fn lights_add_up_to_full_brightness() {
+        let mut light = dark(Vec2::new(5, 1));
+        light.add_light(Vec2::new(1, 0), 3.0);
+        light.add_light(Vec2::new(3, 0), 3.0);
+        assert_near(light.get(Vec2::new(2, 0)), 1.0);
+        assert_near(light.get(Vec2::new(1, 0)), 1.0);
+    }
+
+    #[test]
+    fn lights_are_clipped_at_the_edges() {
+        let mut light = dark(Vec2::new(3, 3));
+        light.add_light(Vec2::new(0, 0), 3.0);
+        light.add_light(Vec2::new(-2, 5), 3.0);
+        assert_near(light.get(Vec2::new(0, 0)), 1.0);
+        assert_near(light.get(Vec2::new(2, 0)), 1.0 / 3.0);
+        assert_near(light.get(Vec2::new(0, 2)), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn nothing_is_darker_than_ambient() {
+        let light = dark(Vec2::new(2, 2));
+        assert_near(light.get(Vec2::new(1, 1)), AMBIENT);
+        assert_near(light.get(Vec2::new(-1, 0)), AMBIENT);
+        assert_near(light.get(Vec2::new(0, 2)), AMBIENT);
+        let col = light.apply(Vec2::new(0, 0), RGB::from_f32(1.0, 0.5, 0.0));
+        assert_near(col.r, AMBIENT);
+        assert_near(col.g, AMBIENT * 0.5);
+        assert_near(col.b, 0.0);
+    }
+}
@@ -1,19 +1,55 @@
+mod character_sheet;
+mod explore;
+mod heightmap;
+mod hotbar;
+mod hud;
+mod input;
+mod keybinding_screen;
+mod keymap;
+mod layout;
 mod lib;
+mod lighting;
+mod memory;
+mod minimap;
+mod pathfinding;
+mod render;
+mod settings;
+mod waypoint_screen;
+mod waypoints;
+mod world_map;
 
 bracket_terminal::add_wasm_support!();
 use bracket_terminal::prelude::*;
 
-use crate::lib::read_arguments;
+use crate::{
+    character_sheet::CharacterSheet,
+    heightmap::HeightmapCache,
+    input::HeldKeys,
+    keybinding_screen::{KeybindingScreen, ScreenEvent},
+    keymap::{Action, KeyBinding},
+    layout::{Layout, LayoutPreset, PanelKind},
+    lib::read_arguments,
+    lighting::LightMap,
+    memory::MapMemory,
+    pathfinding::Travel,
+    render::{Cell, EntityColors, Relation},
+    settings::Settings,
+    waypoint_screen::{WaypointEvent, WaypointScreen},
+    waypoints::{Waypoint, Waypoints},
+    world_map::WorldMap,
+};
 
 use crate::comp::{humanoid, Body};
 use specs::prelude::*;
 use std::{
+    f32::consts::FRAC_PI_4,
     io,
     io::{stdin, stdout, Write},
+    path::{Path, PathBuf},
     process,
     sync::{mpsc, Arc},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::runtime::Runtime;
 use vek::*;
@@ -30,15 +66,43 @@ use veloren_common::{
     comp::InputKind,
     terrain::{
         structure::{self, StructureBlock},
-        Block, BlockKind, SpriteKind,
+        TerrainGrid,
     },
-    uid::UidAllocator,
+    uid::{Uid, UidAllocator},
+    util::Dir,
     vol::ReadVol,
 };
 
+/// Console layers, drawn bottom to top
+const TERRAIN_LAYER: usize = 0;
+const ENTITY_LAYER: usize = 1;
+const HUD_LAYER: usize = 2;
+
+/// How often the map memory is written out while it changes
+const MEMORY_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
 struct State {
-    ecs: World,
+    client: Client,
+    clock: Clock,
+    settings: Settings,
+    settings_path: PathBuf,
     zoom_level: f32,
+    heightmap: HeightmapCache,
+    run_benchmark: bool,
+    /// Run the benchmark as soon as the terrain in view is loaded, then quit
+    benchmark_and_quit: bool,
+    find_resource: bool,
+    memory: MapMemory,
+    waypoints: Waypoints,
+    exploring: bool,
+    last_health: f32,
+    damaged_at: Option<Instant>,
+    character_sheet: Option<CharacterSheet>,
+    keybinding_screen: Option<KeybindingScreen>,
+    world_map: Option<WorldMap>,
+    waypoint_screen: Option<WaypointScreen>,
+    held_keys: HeldKeys,
+    is_ability_active: [bool; hotbar::ABILITY_SLOTS],
     chat_log: Vec<String>,
     chat_input: String,
     chat_input_enabled: bool,
@@ -55,462 +119,1279 @@ struct State {
     arrowedpos: u32,
     swap: bool,
     use_item: bool,
+    travel: Option<Travel>,
+    target: Option<Entity>,
 }
 
-impl GameState for State {
-    fn tick(&mut self, ctx: &mut BTerm) {
-        ctx.cls();
+/// Where the map and HUD panels go this frame, and where the player and mouse pointer are on them
+struct Frame {
+    layout: Layout,
+    /// Size of the HUD console, whose font can differ from the map's
+    hud_size: Vec2<i32>,
+    /// Top left corner of the map on the terrain and entity consoles
+    map_offset: Vec2<i32>,
+    /// Size of the map in cells
+    screen_size: Vec2<u16>,
+    player_pos: Vec3<f32>,
+    /// Map cell under the mouse pointer
+    mouse_pos: Vec2<i32>,
+    mouse_on_map: bool,
+}
 
-        let mut clock = self.ecs.fetch_mut::<Clock>();
-        let mut client = self.ecs.fetch_mut::<Client>();
+impl Frame {
+    /// Lay everything out from the current console sizes, which follow the window size
+    fn new(ctx: &mut BTerm, layout: &LayoutPreset, client: &Client) -> Self {
+        ctx.set_active_console(HUD_LAYER);
+        let hud_size = Vec2::<u32>::from(ctx.get_char_size()).map(|e| e as i32);
+        ctx.set_active_console(TERRAIN_LAYER);
+        let console_size = Vec2::<u32>::from(ctx.get_char_size()).map(|e| e as i32);
+        let layout = Layout::new(layout, console_size);
+        let map_rect = layout
+            .get(PanelKind::Map)
+            .unwrap_or_else(|| Rect::with_size(0, 0, console_size.x, console_size.y));
+        let map_offset = Vec2::new(map_rect.x1, map_rect.y1);
+        let screen_size = Vec2::new(map_rect.width(), map_rect.height()).map(|e| e as u16);
+        let player_pos = client
+            .state()
+            .read_storage::<comp::Pos>()
+            .get(client.entity())
+            .map(|pos| pos.0)
+            .unwrap_or(Vec3::zero());
+        let mouse_pos = Vec2::from(ctx.mouse_pos()) - map_offset;
 
-        {
-            // Get Health and Energy
-            let (current_health, max_health) = client
-                .current::<comp::Health>()
-                .map_or((0.0, 0.0), |health| (health.current(), health.maximum()));
-            let (current_energy, max_energy) = client
-                .current::<comp::Energy>()
-                .map_or((0.0, 0.0), |energy| (energy.current(), energy.maximum()));
-
-            // Invite Logic
-            let (inviter_uid, invite_kind) =
-                if let Some((inviter_uid, _, _, invite_kind)) = client.invite() {
-                    (Some(inviter_uid), Some(invite_kind))
-                } else {
-                    (None, None)
-                };
+        let mut frame = Self {
+            layout,
+            hud_size,
+            map_offset,
+            screen_size,
+            player_pos,
+            mouse_pos,
+            mouse_on_map: false,
+        };
+        frame.mouse_on_map = frame.on_map(mouse_pos);
+        frame
+    }
 
-            //Get entity username from UID
-            let inviter_username = if let Some(uid) = inviter_uid {
-                if let Some(entity) = client
-                    .state()
-                    .ecs()
-                    .read_resource::<UidAllocator>()
-                    .retrieve_entity_internal(uid.id())
-                {
-                    if let Some(player) = client.state().read_storage::<comp::Player>().get(entity)
-                    {
-                        player.alias.clone()
-                    } else {
-                        "".to_string()
-                    }
-                } else {
-                    "".to_string()
+    /// Whether a map cell is on the map
+    fn on_map(&self, scr_pos: Vec2<i32>) -> bool {
+        scr_pos
+            .map2(self.screen_size, |e, sz| e >= 0 && e < sz as i32)
+            .reduce_and()
+    }
+
+    /// Map cell a world position is drawn at
+    fn to_screen_pos(&self, pos: Vec2<f32>, zoom_level: f32) -> Vec2<i32> {
+        ((pos - Vec2::from(self.player_pos)) * Vec2::new(1.0, -1.0) / zoom_level
+            + self.screen_size.map(|e| e as f32) / 2.0)
+            .map(|e| e as i32)
+    }
+
+    /// Middle of the block drawn at a map cell
+    fn to_world_pos(&self, scr_pos: Vec2<i32>, zoom_level: f32) -> Vec2<f32> {
+        (Vec2::from(self.player_pos)
+            + (scr_pos.map(|e| e as f32) - self.screen_size.map(|e| e as f32 / 2.0))
+                * Vec2::new(1.0, -1.0)
+                * zoom_level)
+            .map(|e| e.floor() + 0.5)
+    }
+}
+
+impl State {
+    /// Whether the game has the keys, rather than the keybinding screen, world map or waypoint
+    /// list
+    fn playing(&self) -> bool {
+        self.keybinding_screen.is_none()
+            && self.world_map.is_none()
+            && self.waypoint_screen.is_none()
+    }
+
+    /// Handle the pressed key, clicks and travel, and send the actions held this frame
+    ///
+    /// Returns the movement and look direction to tick the client with.
+    fn handle_input(&mut self, ctx: &BTerm, frame: &Frame) -> comp::ControllerInputs {
+        let mut inputs = comp::ControllerInputs::default();
+
+        // Catch up on held keys, clicks and typed text before the pressed key is handled, so
+        // the key that starts typing a name doesn't end up in it
+        self.held_keys.update();
+        if let Some(screen) = self.waypoint_screen.as_mut() {
+            screen.type_text(self.held_keys.typed());
+        }
+        self.handle_key(ctx, frame);
+
+        // Movement and actions last for as long as their keys are held, except while a screen
+        // is taking keys
+        let playing = self.playing();
+        if playing {
+            // Keys used by open windows don't move, so arrows can both move and scroll
+            let mut reserved = Vec::new();
+            if self.inv_toggle {
+                for action in &[
+                    Action::InventoryUp,
+                    Action::InventoryDown,
+                    Action::InventorySelect,
+                    Action::InventoryUse,
+                ] {
+                    reserved.extend(self.settings.keymap.bindings(*action));
                 }
-            } else {
-                "".to_string()
-            };
+            }
+            if self.character_sheet.is_some() {
+                for action in Action::ALL.iter().filter(|action| action.is_menu()) {
+                    reserved.extend(self.settings.keymap.bindings(*action));
+                }
+            }
+            inputs.move_dir = self.settings.keymap.move_dir(&self.held_keys, &reserved);
 
-            //Get player pos
-            let screen_size = Vec2::new(80, 50);
-            let player_pos = client
+            // Left click walks to the clicked block, right click targets whatever stands on it,
+            // unless the character sheet covers the map
+            let on_map = frame.mouse_on_map && self.character_sheet.is_none();
+            if on_map && self.held_keys.clicked(0) {
+                let goal = frame.to_world_pos(frame.mouse_pos, self.zoom_level);
+                let goal = Vec3::new(goal.x, goal.y, frame.player_pos.z).map(|e| e.floor() as i32);
+                match pathfinding::find_path(
+                    &self.client.state().terrain(),
+                    frame.player_pos.map(|e| e.floor() as i32),
+                    goal,
+                    true,
+                ) {
+                    Some(path) => self.travel = Some(Travel::new(path)),
+                    None => self.chat_log.push("No way there".to_string()),
+                }
+            }
+            if on_map && self.held_keys.clicked(1) {
+                let state = self.client.state();
+                self.target = (
+                    &state.ecs().entities(),
+                    &state.ecs().read_storage::<comp::Pos>(),
+                    &state.ecs().read_storage::<comp::Body>(),
+                )
+                    .join()
+                    .filter(|(entity, pos, _)| {
+                        *entity != self.client.entity()
+                            && frame.to_screen_pos(Vec2::from(pos.0), self.zoom_level)
+                                == frame.mouse_pos
+                    })
+                    .min_by(|(_, a, _), (_, b, _)| {
+                        a.0.distance_squared(frame.player_pos)
+                            .partial_cmp(&b.0.distance_squared(frame.player_pos))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|(entity, _, _)| entity);
+            }
+        }
+
+        // Follow the path being travelled until there, or until moving by hand, jumping up
+        // steps on the way
+        let mut travel_jump = false;
+        if inputs.move_dir != Vec2::zero() {
+            self.travel = None;
+            self.exploring = false;
+        } else if let Some(travel) = self.travel.as_mut() {
+            match travel.move_dir(frame.player_pos) {
+                Some(dir) => {
+                    inputs.move_dir = dir;
+                    travel_jump = travel.needs_jump(frame.player_pos);
+                }
+                None => self.travel = None,
+            }
+        }
+
+        // Face the target so attacks go its way, forgetting it once it's gone
+        let target_pos = self.target.and_then(|target| {
+            self.client
                 .state()
                 .read_storage::<comp::Pos>()
-                .get(client.entity())
+                .get(target)
                 .map(|pos| pos.0)
-                .unwrap_or(Vec3::zero());
-            let to_screen_pos = |pos: Vec2<f32>, zoom_level: f32| {
-                ((pos - Vec2::from(player_pos)) * Vec2::new(1.0, -1.0) / zoom_level
-                    + screen_size.map(|e| e as f32) / 2.0)
-                    .map(|e| e as i32)
-            };
+        });
+        match target_pos {
+            Some(pos) => {
+                if let Some(dir) = Dir::from_unnormalized(pos - frame.player_pos) {
+                    inputs.look_dir = dir;
+                }
+            }
+            None => self.target = None,
+        }
+        let mut actions = vec![
+            (InputKind::Jump, Action::Jump, &mut self.is_jump_active),
+            (
+                InputKind::Primary,
+                Action::Primary,
+                &mut self.is_primary_active,
+            ),
+            (
+                InputKind::Secondary,
+                Action::Secondary,
+                &mut self.is_secondary_active,
+            ),
+        ];
+        for (i, active) in self.is_ability_active.iter_mut().enumerate() {
+            actions.push((InputKind::Ability(i), Action::ABILITIES[i], active));
+        }
+        for (kind, action, active) in actions {
+            let held = (playing && self.settings.keymap.is_held(action, &self.held_keys))
+                || (action == Action::Jump && travel_jump);
+            if held != *active {
+                self.client.handle_input(kind, held, None, None);
+                *active = held;
+            }
+        }
 
-            let mut inputs = comp::ControllerInputs::default();
-
-            // Handle inputs
-            match ctx.key {
-                None => {} // Nothing happened
-                Some(key) => {
-                    // A key is pressed or held
-                    match key {
-                        // Chat
-                        VirtualKeyCode::C if self.chat_input_enabled => match key {
-                            VirtualKeyCode::Return => {
-                                if self.chat_input.is_empty() {
-                                } else {
-                                    if self.chat_input.clone().starts_with('/') {
-                                        let mut argv = self.chat_input.clone();
-                                        client.send_command(
-                                            argv.split_whitespace().next().unwrap().to_owned(),
-                                            argv.split_whitespace().map(|s| s.to_owned()).collect(),
-                                        );
-                                    } else {
-                                        client.send_chat(self.chat_input.clone())
-                                    }
-                                    self.chat_input = String::new();
+        inputs
+    }
+
+    /// Act on the key pressed this frame, if any, sending it to whichever screen is open
+    fn handle_key(&mut self, ctx: &BTerm, frame: &Frame) {
+        let client = &mut self.client;
+        match ctx.key {
+            None => {} // Nothing happened
+            Some(key) if self.keybinding_screen.is_some() => {
+                // Keys go to the keybinding screen while it is open
+                let binding = KeyBinding::new(key, ctx.shift, ctx.control, ctx.alt);
+                if let Some(screen) = self.keybinding_screen.as_mut() {
+                    match screen.handle_key(binding, &mut self.settings.keymap) {
+                        ScreenEvent::Changed => {
+                            // Only the keymap is saved, not options overridden from the
+                            // command line, and never over a file that didn't parse
+                            let path = self.settings_path.as_path();
+                            match Settings::read(path) {
+                                Ok(saved) => {
+                                    let mut saved = saved.unwrap_or_default();
+                                    saved.keymap = self.settings.keymap.clone();
+                                    saved.save(path);
                                 }
-                                self.chat_input_enabled = false;
+                                Err(err) => self
+                                    .chat_log
+                                    .push(format!("{}, keybindings not saved", err)),
                             }
-                            VirtualKeyCode::Back => {
-                                self.chat_input.pop();
-                            }
-                            _key => self
-                                .chat_input
-                                .push(format!("{}", key as i32).pop().unwrap()),
-                        },
-
-                        // Numpad
-                        VirtualKeyCode::Numpad8 | VirtualKeyCode::W => inputs.move_dir.y += 1.0,
-                        VirtualKeyCode::Numpad4 | VirtualKeyCode::A => inputs.move_dir.x -= 1.0,
-                        VirtualKeyCode::Numpad2 | VirtualKeyCode::Numpad5 | VirtualKeyCode::S => {
-                            inputs.move_dir.y -= 1.0
-                        }
-                        VirtualKeyCode::Numpad6 | VirtualKeyCode::D => inputs.move_dir.x += 1.0,
-                        VirtualKeyCode::Numpad7 => {
-                            inputs.move_dir.x -= 1.0;
-                            inputs.move_dir.y += 1.0;
                         }
-                        VirtualKeyCode::Numpad9 => {
-                            inputs.move_dir.x += 1.0;
-                            inputs.move_dir.y += 1.0;
-                        }
-                        VirtualKeyCode::Numpad1 => {
-                            inputs.move_dir.x -= 1.0;
-                            inputs.move_dir.y -= 1.0;
-                        }
-                        VirtualKeyCode::Numpad3 => {
-                            inputs.move_dir.x += 1.0;
-                            inputs.move_dir.y -= 1.0;
+                        ScreenEvent::Close => self.keybinding_screen = None,
+                        ScreenEvent::None => {}
+                    }
+                }
+            }
+            Some(key) if self.world_map.is_some() => {
+                // And to the world map while it is open, its own key closes it again and
+                // waypoints go under its cursor
+                let binding = KeyBinding::new(key, ctx.shift, ctx.control, ctx.alt);
+                let actions = self.settings.keymap.actions(binding);
+                if let Some(map) = self.world_map.as_mut() {
+                    if actions.contains(&Action::AddWaypoint) {
+                        let waypoint = Waypoint {
+                            name: self.waypoints.next_name(),
+                            pos: map.cursor().map(|e| e.floor() as i32),
+                            alt: None,
+                        };
+                        let name = waypoint.name.clone();
+                        if self.waypoints.add(waypoint) {
+                            self.chat_log.push(format!("[Waypoint] Added {}", name));
                         }
-
-                        VirtualKeyCode::Down => self.invpos += 1,
-                        VirtualKeyCode::Up => self.invpos -= 1,
-                        VirtualKeyCode::Right => match self.arrowedpos {
-                            0 => {
-                                self.arrowed1 = self.arrowed;
-                                self.arrowedpos = 1;
-                                self.swap = false;
-                            }
-                            1 => {
-                                self.arrowed2 = self.arrowed;
-                                self.arrowedpos = 2;
-                            }
-                            _ => {
-                                self.swap = true;
-                                self.arrowedpos = 2;
+                    } else if actions.contains(&Action::WorldMap)
+                        || map.handle_key(key, Vec2::from(frame.player_pos))
+                    {
+                        self.world_map = None;
+                    }
+                }
+            }
+            Some(key) if self.waypoint_screen.is_some() => {
+                // And to the waypoint list, where names are typed too
+                let binding = KeyBinding::new(key, ctx.shift, ctx.control, ctx.alt);
+                let actions = self.settings.keymap.actions(binding);
+                let event = match self.waypoint_screen.as_mut() {
+                    Some(screen) => screen.handle_key(key, &actions, &mut self.waypoints),
+                    None => WaypointEvent::None,
+                };
+                match event {
+                    WaypointEvent::Travel(i) => {
+                        let waypoint = &self.waypoints.list()[i];
+                        let goal = Vec3::new(
+                            waypoint.pos.x,
+                            waypoint.pos.y,
+                            waypoint.alt.unwrap_or(frame.player_pos.z.floor() as i32),
+                        );
+                        match pathfinding::find_path(
+                            &client.state().terrain(),
+                            frame.player_pos.map(|e| e.floor() as i32),
+                            goal,
+                            true,
+                        ) {
+                            Some(path) => {
+                                self.travel = Some(Travel::new(path));
+                                self.exploring = false;
+                                self.waypoint_screen = None;
                             }
-                        },
-                        VirtualKeyCode::Left => {
-                            self.use_slotid = self.arrowed;
-                            self.use_item = true;
+                            None => self
+                                .chat_log
+                                .push(format!("No way to {} from here", waypoint.name)),
                         }
-
-                        VirtualKeyCode::U => client.accept_invite(),
-                        VirtualKeyCode::I => client.decline_invite(),
-                        VirtualKeyCode::T => self.inv_toggle = !self.inv_toggle,
-                        VirtualKeyCode::Space => {
-                            if self.is_jump_active {
-                                client.handle_input(InputKind::Jump, false, None, None);
-                                self.is_jump_active = false;
+                    }
+                    WaypointEvent::Share(i) => {
+                        // The whole message is the one argument of the group command
+                        let message = self.waypoints.list()[i].share_message();
+                        client.send_command("group".to_string(), vec![message]);
+                    }
+                    WaypointEvent::Close => self.waypoint_screen = None,
+                    WaypointEvent::None => {}
+                }
+            }
+            Some(key) => {
+                // A key is pressed or held
+                let binding = KeyBinding::new(key, ctx.shift, ctx.control, ctx.alt);
+                match key {
+                    // Chat
+                    VirtualKeyCode::C if self.chat_input_enabled => match key {
+                        VirtualKeyCode::Return => {
+                            if self.chat_input.is_empty() {
                             } else {
-                                client.handle_input(InputKind::Jump, true, None, None);
-                                self.is_jump_active = true;
+                                if self.chat_input.clone().starts_with('/') {
+                                    let mut argv = self.chat_input.clone();
+                                    client.send_command(
+                                        argv.split_whitespace().next().unwrap().to_owned(),
+                                        argv.split_whitespace().map(|s| s.to_owned()).collect(),
+                                    );
+                                } else {
+                                    client.send_chat(self.chat_input.clone())
+                                }
+                                self.chat_input = String::new();
                             }
+                            self.chat_input_enabled = false;
                         }
-                        VirtualKeyCode::X => {
-                            if self.is_primary_active {
-                                client.handle_input(InputKind::Primary, false, None, None);
-                                self.is_primary_active = false;
-                            } else {
-                                client.handle_input(InputKind::Primary, true, None, None);
-                                self.is_primary_active = true;
-                            }
+                        VirtualKeyCode::Back => {
+                            self.chat_input.pop();
                         }
-                        VirtualKeyCode::Z => {
-                            if self.is_secondary_active {
-                                client.handle_input(InputKind::Secondary, false, None, None);
-                                self.is_secondary_active = false;
-                            } else {
-                                client.handle_input(InputKind::Secondary, true, None, None);
-                                self.is_secondary_active = true;
+                        _key => self
+                            .chat_input
+                            .push(format!("{}", key as i32).pop().unwrap()),
+                    },
+
+                    _ => {
+                        let actions = self.settings.keymap.actions(binding);
+                        // The character sheet covers the inventory and takes its keys
+                        let in_sheet = self.character_sheet.is_some()
+                            && actions.iter().any(|action| action.is_menu());
+                        let in_inventory = self.inv_toggle && !in_sheet;
+                        for action in actions {
+                            match action {
+                                Action::CharacterSheet => {
+                                    self.character_sheet = match self.character_sheet {
+                                        Some(_) => None,
+                                        None => Some(CharacterSheet::default()),
+                                    }
+                                }
+                                Action::WorldMap => {
+                                    self.world_map = Some(WorldMap::new(
+                                        Vec2::from(frame.player_pos),
+                                        &client.world_data().lod_alt,
+                                    ))
+                                }
+                                Action::AddWaypoint => {
+                                    let waypoint = Waypoint {
+                                        name: self.waypoints.next_name(),
+                                        pos: Vec2::from(frame.player_pos)
+                                            .map(|e: f32| e.floor() as i32),
+                                        alt: Some(frame.player_pos.z.floor() as i32),
+                                    };
+                                    let name = waypoint.name.clone();
+                                    if self.waypoints.add(waypoint) {
+                                        self.chat_log.push(format!("[Waypoint] Added {}", name));
+                                    } else {
+                                        self.chat_log.push(
+                                            "[Waypoint] There's one here already".to_string(),
+                                        );
+                                    }
+                                }
+                                Action::Waypoints => {
+                                    self.waypoint_screen = Some(WaypointScreen::default())
+                                }
+                                Action::Keybindings => {
+                                    self.keybinding_screen = Some(KeybindingScreen::default())
+                                }
+
+                                Action::MenuUp
+                                | Action::MenuDown
+                                | Action::MenuLeft
+                                | Action::MenuRight
+                                | Action::MenuSelect => {
+                                    if let (Some(sheet), Some(skill_set)) = (
+                                        self.character_sheet.as_mut(),
+                                        client.current::<comp::SkillSet>(),
+                                    ) {
+                                        if let Some(skill) = sheet.handle_action(action, &skill_set)
+                                        {
+                                            client.unlock_skill(skill);
+                                        }
+                                    }
+                                }
+                                Action::InventoryDown if in_inventory => self.invpos += 1,
+                                Action::InventoryUp if in_inventory => {
+                                    self.invpos = self.invpos.saturating_sub(1)
+                                }
+                                Action::InventorySelect if in_inventory => match self.arrowedpos {
+                                    0 => {
+                                        self.arrowed1 = self.arrowed;
+                                        self.arrowedpos = 1;
+                                        self.swap = false;
+                                    }
+                                    1 => {
+                                        self.arrowed2 = self.arrowed;
+                                        self.arrowedpos = 2;
+                                    }
+                                    _ => {
+                                        self.swap = true;
+                                        self.arrowedpos = 2;
+                                    }
+                                },
+                                Action::InventoryUse if in_inventory => {
+                                    self.use_slotid = self.arrowed;
+                                    self.use_item = true;
+                                }
+
+                                Action::AcceptInvite => client.accept_invite(),
+                                Action::DeclineInvite => client.decline_invite(),
+                                Action::ToggleInventory => self.inv_toggle = !self.inv_toggle,
+                                Action::Glide => {
+                                    client.toggle_glide();
+                                    self.is_glide_active = !self.is_glide_active
+                                    //do_glide = !do_glide,
+                                }
+                                Action::Lantern => {
+                                    // Going by the server, which also puts lanterns out on its own
+                                    if client.current::<comp::LightEmitter>().is_some() {
+                                        client.disable_lantern();
+                                    } else {
+                                        client.enable_lantern();
+                                    }
+                                }
+                                Action::Respawn => client.respawn(),
+                                Action::Benchmark => self.run_benchmark = true,
+                                Action::FindResource => self.find_resource = true,
+                                Action::Explore => {
+                                    self.exploring = !self.exploring;
+                                    self.travel = None;
+                                }
+                                Action::ZoomIn => self.zoom_level /= 1.5,
+                                Action::ZoomOut => self.zoom_level *= 1.5,
+
+                                _ => {} // Held actions are handled below
                             }
                         }
-                        VirtualKeyCode::G => {
-                            client.toggle_glide();
-                            self.is_glide_active = !self.is_glide_active //do_glide = !do_glide,
-                        }
-                        VirtualKeyCode::R => client.respawn(),
-                        VirtualKeyCode::Plus => self.zoom_level /= 1.5,
-                        VirtualKeyCode::Minus => self.zoom_level *= 1.5,
-
-                        _ => {} // Ignore all the other possibilities
                     }
                 }
             }
+        }
+    }
 
-            let mut events = client.tick(inputs, clock.dt(), |_| ()).unwrap();
-            let mut inventory_storage = client.state().ecs().read_storage::<comp::Inventory>();
-            let mut inventory = inventory_storage.get(client.entity());
-            // Tick client
-            for event in events {
-                match event {
-                    Event::Chat(msg) => match msg.chat_type {
-                        comp::ChatType::World(_) => self.chat_log.push(msg.message),
-                        comp::ChatType::Group(_, _) => {
-                            self.chat_log.push(format!("[Group] {}", msg.message))
+    /// Act on what the server sent this tick
+    fn handle_events(&mut self, events: Vec<Event>) {
+        for event in events {
+            match event {
+                Event::Disconnect => {
+                    self.memory.save();
+                    self.chat_log
+                        .push("Disconnected from the server".to_string());
+                }
+                Event::Chat(msg) => match msg.chat_type {
+                    comp::ChatType::World(_) => self.chat_log.push(msg.message),
+                    comp::ChatType::Group(_, _) => {
+                        self.chat_log.push(format!("[Group] {}", msg.message));
+                        // Coordinates from the group become waypoints, such as those shared
+                        // from the waypoint list
+                        if let Some(mut waypoint) = waypoints::parse(&msg.message) {
+                            if waypoint.name.is_empty() {
+                                waypoint.name = self.waypoints.next_name();
+                            }
+                            let name = waypoint.name.clone();
+                            if self.waypoints.add(waypoint) {
+                                self.chat_log
+                                    .push(format!("[Waypoint] Added {} from group chat", name));
+                            }
                         }
-                        _ => {}
-                    },
+                    }
                     _ => {}
-                }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Scan the terrain in view and act on it: remember it, find resources and explore
+    ///
+    /// Returns the world position of every map cell and what is seen there.
+    fn scan_map(
+        &mut self,
+        ctx: &mut BTerm,
+        frame: &Frame,
+        took_damage: bool,
+    ) -> (Vec<Vec3<i32>>, Vec<Cell>) {
+        let state = self.client.state();
+        let terrain = state.terrain();
+
+        self.heightmap.invalidate(&state.terrain_changes());
+
+        // World position of every cell on screen
+        let mut wposes =
+            Vec::with_capacity(frame.screen_size.x as usize * frame.screen_size.y as usize);
+        for y in 0..frame.screen_size.y {
+            for x in 0..frame.screen_size.x {
+                wposes.push(
+                    (frame.player_pos
+                        + Vec3::new(x, y, 0).map2(frame.screen_size.into(), |e, sz: u16| {
+                            e as f32 - sz as f32 / 2.0
+                        }) * Vec2::new(1.0, -1.0)
+                            * self.zoom_level)
+                        .map(|e| e.floor() as i32),
+                );
+            }
+        }
+
+        if self.benchmark_and_quit
+            && wposes.iter().all(|wpos| {
+                terrain
+                    .get_key(TerrainGrid::chunk_key(Vec2::from(*wpos)))
+                    .is_some()
+            })
+        {
+            self.run_benchmark = true;
+        }
+        if self.run_benchmark {
+            let (scan, cached, parallel) = heightmap::benchmark(
+                &terrain,
+                &wposes,
+                frame.screen_size.x as usize,
+                self.zoom_level,
+                30,
+            );
+            let result = format!(
+                "[Bench] scan: {:.2} ms, cached: {:.2} ms, parallel: {:.2} ms per frame",
+                scan.as_secs_f64() * 1000.0,
+                cached.as_secs_f64() * 1000.0,
+                parallel.as_secs_f64() * 1000.0
+            );
+            if self.benchmark_and_quit {
+                println!("{}", result);
+                ctx.quit();
+            }
+            self.chat_log.push(result);
+            self.run_benchmark = false;
+        }
+
+        // Top visible block of every cell, scanning only columns not cached yet
+        let cells = self.heightmap.scan_rows(
+            &terrain,
+            &wposes,
+            frame.screen_size.x as usize,
+            self.zoom_level,
+        );
+
+        // Find the way again after being pushed off the path
+        let start = frame.player_pos.map(|e| e.floor() as i32);
+        if let Some(destination) = self
+            .travel
+            .as_ref()
+            .filter(|travel| travel.is_lost(frame.player_pos))
+            .and_then(|travel| travel.destination())
+        {
+            self.travel = pathfinding::find_path(&terrain, start, destination, !self.exploring)
+                .map(Travel::new);
+        }
+
+        // Travel to the nearest resource in view
+        if self.find_resource {
+            let nearest = cells
+                .iter()
+                .zip(&wposes)
+                .filter_map(|(cell, wpos)| {
+                    // Zoomed out cells are named after their top left column, so find
+                    // the block the sprite is really in
+                    let sprite = cell
+                        .sprite
+                        .filter(|sprite| render::sprite_rank(*sprite) > 0)?;
+                    render::find_sprite(&terrain, *wpos, self.zoom_level, sprite)
+                        .map(|wpos| (sprite, wpos))
+                })
+                .min_by(|(_, a), (_, b)| {
+                    let dist = |wpos: &Vec3<i32>| {
+                        wpos.map(|e| e as f32).distance_squared(frame.player_pos)
+                    };
+                    dist(a)
+                        .partial_cmp(&dist(b))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            match nearest {
+                Some((sprite, wpos)) => match pathfinding::find_path(&terrain, start, wpos, true) {
+                    Some(path) => self.travel = Some(Travel::new(path)),
+                    None => self.chat_log.push(format!("No way to the {:?}", sprite)),
+                },
+                None => self.chat_log.push("No resources in view".to_string()),
             }
+            self.find_resource = false;
+        }
 
-            // Rendering view
+        // Remember every loaded column in view, noting resources never seen before
+        let mut found = None;
+        for (cell, wpos) in cells.iter().zip(&wposes) {
+            if terrain
+                .get_key(TerrainGrid::chunk_key(Vec2::from(*wpos)))
+                .is_none()
             {
-                let state = client.state();
-                let level_tint = [0.15, 0.30, 0.50];
-                let terrain = state.terrain();
-
-                // Render block
-                for y in 0..screen_size.y {
-                    for x in 0..screen_size.x {
-                        let wpos = (player_pos
-                            + Vec3::new(x, y, 0)
-                                .map2(screen_size.into(), |e, sz: u16| e as f32 - sz as f32 / 2.0)
-                                * Vec2::new(1.0, -1.0)
-                                * self.zoom_level)
-                            .map(|e| e.floor() as i32);
-
-                        let mut block_z = 0;
-                        let mut block = None;
-                        let mut block_char = None;
-
-                        let elevation = 16;
-                        for (k, z) in (-2..elevation).enumerate() {
-                            let block_pos = wpos + Vec3::unit_z() * -z;
-                            block_z = block_pos.z;
-
-                            if let Ok(b) = terrain.get(block_pos) {
-                                let sprite = b.get_sprite();
-                                if sprite.is_some() && sprite.unwrap() != SpriteKind::Empty {
-                                    let sprite2 = sprite.unwrap();
-                                    let flower1 =
-                                        SpriteKind::BarrelCactus as u8..=SpriteKind::Turnip as u8;
-                                    let flower2 = SpriteKind::LargeGrass as u8
-                                        ..=SpriteKind::LargeCactus as u8;
-                                    let furniture = SpriteKind::Window1 as u8
-                                        ..=SpriteKind::WardrobeDouble as u8;
-                                    block_char = match sprite2 {
-                                        SpriteKind::Apple => Some('a'),
-                                        SpriteKind::Sunflower => Some('u'),
-                                        SpriteKind::Mushroom => Some('m'),
-                                        SpriteKind::Velorite | SpriteKind::VeloriteFrag => {
-                                            Some('v')
-                                        }
-                                        SpriteKind::Chest | SpriteKind::Crate => Some('c'),
-                                        SpriteKind::Stones => Some('"'),
-                                        SpriteKind::Twigs => Some(';'),
-                                        SpriteKind::Amethyst | SpriteKind::Ruby => Some('☼'), // TODO: add more
-                                        SpriteKind::Beehive => Some('b'),
-                                        _ => {
-                                            let sprite3 = sprite2 as u8;
-                                            if flower1.contains(&sprite3)
-                                                || flower2.contains(&sprite3)
-                                            {
-                                                Some('♣')
-                                            } else if furniture.contains(&sprite3) {
-                                                match sprite2 {
-                                                    SpriteKind::Bed => Some('Θ'),
-                                                    SpriteKind::Bench
-                                                    | SpriteKind::ChairSingle
-                                                    | SpriteKind::ChairDouble => Some('╥'),
-                                                    SpriteKind::TableSide
-                                                    | SpriteKind::TableDining
-                                                    | SpriteKind::TableDouble => Some('╤'),
-                                                    _ => Some('π'),
-                                                }
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                    };
-                                } else if b.is_filled() {
-                                    block = Some(*b);
-                                    if block_char.is_none() {
-                                        let ublock = block.unwrap();
-                                        block_char = match ublock.kind() {
-                                            BlockKind::Air => Some(' '),
-                                            BlockKind::Water => Some('≈'),
-                                            BlockKind::Rock => Some('o'),
-                                            BlockKind::WeakRock => Some('.'),
-                                            BlockKind::Lava => Some('≈'),
-                                            BlockKind::GlowingRock => Some('*'),
-                                            BlockKind::GlowingWeakRock => Some('.'),
-                                            BlockKind::Grass => Some(','),
-                                            BlockKind::Snow => Some('≈'),
-                                            BlockKind::Earth => Some('0'),
-                                            BlockKind::Sand => Some('▓'),
-                                            BlockKind::Wood => Some('≡'),
-                                            BlockKind::Leaves => Some('♠'),
-                                            BlockKind::Misc => Some('#'),
-                                        };
-                                    }
-                                    break;
-                                }
-                            }
-                        }
+                continue;
+            }
+            // Zoomed out cells stand for many columns, so remember the column itself
+            let column = if self.zoom_level <= 1.0 {
+                *cell
+            } else {
+                self.heightmap.get(&terrain, *wpos)
+            };
+            if column.kind.is_some() && self.memory.remember(Vec2::from(*wpos), &column) {
+                found = found.or(column
+                    .sprite
+                    .filter(|sprite| render::sprite_rank(*sprite) > 0));
+            }
+        }
+        self.memory.save_every(MEMORY_SAVE_INTERVAL);
 
-                        let level_down = (wpos.z - 2 - block_z) as usize;
+        // Explore until there's nothing left nearby or something turns up
+        if self.exploring {
+            let bodies = state.ecs().read_storage::<comp::Body>();
+            let hostiles: Vec<Vec3<f32>> = (
+                &state.ecs().entities(),
+                &state.ecs().read_storage::<comp::Pos>(),
+                &state.ecs().read_storage::<comp::Alignment>(),
+            )
+                .join()
+                .filter(|(entity, _, alignment)| render::is_hostile(alignment, bodies.get(*entity)))
+                .map(|(_, pos, _)| pos.0)
+                .collect();
 
-                        let col: RGB = match block {
-                            Some(block) => match block {
-                                _ => {
-                                    let rgb = block.get_color().unwrap();
+            let stop = if took_damage {
+                Some("took damage".to_string())
+            } else if hostiles
+                .iter()
+                .any(|pos| pos.distance(frame.player_pos) < explore::SIGHT_RADIUS as f32)
+            {
+                Some("enemy in sight".to_string())
+            } else {
+                found.map(|sprite| format!("found a {:?}", sprite))
+            };
+            if let Some(reason) = stop {
+                self.chat_log.push(format!("[Explore] Stopped, {}", reason));
+                self.exploring = false;
+                self.travel = None;
+            } else if self.travel.is_none() {
+                match explore::find_unexplored(&self.memory, &terrain, start, &hostiles) {
+                    Some(path) => self.travel = Some(Travel::new(path)),
+                    None => {
+                        self.chat_log
+                            .push("[Explore] Nothing left to explore nearby".to_string());
+                        self.exploring = false;
+                    }
+                }
+            }
+        }
 
-                                    let tint: f32 = if level_down < level_tint.len() {
-                                        level_tint[level_down as usize]
-                                    } else {
-                                        1.0
-                                    };
+        (wposes, cells)
+    }
 
-                                    if tint != 1.0 {
-                                        RGB::from_u8(
-                                            rgb.r + (tint * ((255 - rgb.r) as f32)) as u8,
-                                            rgb.g + (tint * ((255 - rgb.g) as f32)) as u8,
-                                            rgb.b + (tint * ((255 - rgb.b) as f32)) as u8,
-                                        )
-                                    } else {
-                                        RGB::from_u8(rgb.r, rgb.g, rgb.b)
-                                    }
-                                }
-                            },
-                            None => RGB::named(YELLOW),
-                        };
+    /// Draw the terrain, entities and what's under the pointer, and the panels listing what's
+    /// around
+    fn draw_map(&mut self, ctx: &mut BTerm, frame: &Frame, wposes: &[Vec3<i32>], cells: &[Cell]) {
+        let playing = self.playing();
+        let client = &self.client;
+        let state = client.state();
+        let terrain = state.terrain();
+        let sky = lighting::daylight(state.get_time_of_day());
+        let start = frame.player_pos.map(|e| e.floor() as i32);
 
-                        if block_char.is_none() {
-                            block_char = Some('?');
-                        }
+        // Light from glowing blocks and sprites, then from light emitting entities
+        let mut lights = LightMap::new(
+            frame.screen_size.map(|e| e as i32),
+            sky,
+            cells.iter().map(|cell| cell.roofed),
+        );
+        for (i, cell) in cells.iter().enumerate() {
+            if cell.glow > 0 {
+                let pos = Vec2::new(
+                    i as i32 % frame.screen_size.x as i32,
+                    i as i32 / frame.screen_size.x as i32,
+                );
+                lights.add_light(pos, cell.glow as f32 / self.zoom_level);
+            }
+        }
+        let emitters = state.ecs().read_storage::<comp::LightEmitter>();
+        for (pos, emitter) in (&state.ecs().read_storage::<comp::Pos>(), &emitters).join() {
+            let scr_pos = frame.to_screen_pos(Vec2::from(pos.0), self.zoom_level);
+            lights.add_light(
+                scr_pos,
+                lighting::emitter_range(emitter.strength) / self.zoom_level,
+            );
+        }
+
+        // Render block, or what's remembered of it where the terrain isn't loaded
+        for (i, cell) in cells.iter().enumerate() {
+            let x = i as u16 % frame.screen_size.x;
+            let y = i as u16 / frame.screen_size.x;
+            let wpos = Vec2::from(wposes[i]);
+            let loaded = terrain.get_key(TerrainGrid::chunk_key(wpos)).is_some();
+            let (glyph, col) = match self.memory.get(wpos).filter(|_| !loaded) {
+                Some(remembered) => (remembered.glyph, remembered.faded()),
+                None => (
+                    cell.glyph,
+                    lights.apply(Vec2::new(x as i32, y as i32), cell.col),
+                ),
+            };
+            ctx.print_color(
+                frame.map_offset.x + x as i32,
+                frame.map_offset.y + y as i32,
+                col,
+                RGB::named(BLACK),
+                glyph,
+            );
+        }
+
+        ctx.set_active_console(ENTITY_LAYER);
+        let objs = state.ecs().entities();
+        let positions = state.ecs().read_storage::<comp::Pos>();
+        let bodies = state.ecs().read_storage::<comp::Body>();
+        let stats = state.ecs().read_storage::<comp::Stats>();
+        let alignments = state.ecs().read_storage::<comp::Alignment>();
+        let players = state.ecs().read_storage::<comp::Player>();
+        let entity_uids = state.ecs().read_storage::<Uid>();
+        let uid_allocator = state.ecs().read_resource::<UidAllocator>();
+        let player_relation = |entity: Entity| {
+            if entity == client.entity() {
+                Some(Relation::Me)
+            } else if entity_uids
+                .get(entity)
+                .map_or(false, |uid| client.group_members().contains_key(uid))
+            {
+                Some(Relation::GroupMember)
+            } else if players.get(entity).is_some() {
+                Some(Relation::OtherPlayer)
+            } else {
+                None
+            }
+        };
+        let relation = |entity: Entity| {
+            player_relation(entity).or_else(|| {
+                let alignment = alignments.get(entity)?;
+                // Pets side with whoever owns them, looked up one level deep
+                let owner = match alignment {
+                    comp::Alignment::Owned(uid) => uid_allocator
+                        .retrieve_entity_internal(uid.id())
+                        .and_then(|owner| {
+                            player_relation(owner).or_else(|| {
+                                alignments.get(owner).map(|alignment| {
+                                    Relation::from_alignment(alignment, bodies.get(owner), None)
+                                })
+                            })
+                        }),
+                    _ => None,
+                };
+                Some(Relation::from_alignment(
+                    alignment,
+                    bodies.get(entity),
+                    owner,
+                ))
+            })
+        };
+        let mut nearby = Vec::new();
+        let mut hovered = None;
+
+        // Path being travelled, under the entities walking on it
+        for block in self.travel.iter().flat_map(|travel| travel.remaining()) {
+            let scr_pos =
+                frame.to_screen_pos(block.map(|e| e as f32 + 0.5).into(), self.zoom_level);
+            if frame.on_map(scr_pos) {
+                ctx.print_color(
+                    frame.map_offset.x + scr_pos.x,
+                    frame.map_offset.y + scr_pos.y,
+                    RGB::named(YELLOW),
+                    RGB::named(BLACK),
+                    '·',
+                );
+            }
+        }
+
+        // Waypoints, under the entities standing on them
+        for waypoint in self.waypoints.list() {
+            let scr_pos = frame.to_screen_pos(waypoint.center(), self.zoom_level);
+            if frame.on_map(scr_pos) {
+                ctx.print_color(
+                    frame.map_offset.x + scr_pos.x,
+                    frame.map_offset.y + scr_pos.y,
+                    RGB::named(MAGENTA),
+                    RGB::named(BLACK),
+                    '♦',
+                );
+            }
+        }
 
-                        ctx.print_color(x, y, col, RGB::named(BLACK), block_char.unwrap());
+        // Cone of the cells in front of the player with an arrow the way they face, under
+        // anything standing there, which is where attacks go
+        if let Some(dir) = client
+            .current::<comp::Ori>()
+            .and_then(|ori| Vec2::<f32>::from(ori.look_vec()).try_normalized())
+        {
+            let player_scr_pos = frame.to_screen_pos(Vec2::from(frame.player_pos), self.zoom_level);
+            let angle = dir.y.atan2(dir.x);
+            for spread in &[0.0, -FRAC_PI_4, FRAC_PI_4] {
+                let step = Vec2::new((angle + spread).cos(), -(angle + spread).sin())
+                    .map(|e| e.round() as i32);
+                let scr_pos = player_scr_pos + step;
+                if frame.on_map(scr_pos) {
+                    if *spread == 0.0 {
+                        ctx.print_color(
+                            frame.map_offset.x + scr_pos.x,
+                            frame.map_offset.y + scr_pos.y,
+                            RGB::named(YELLOW),
+                            RGB::named(BLACK),
+                            render::facing_glyph(dir),
+                        );
                     }
+                    ctx.set_active_console(TERRAIN_LAYER);
+                    ctx.set_bg(
+                        frame.map_offset.x + scr_pos.x,
+                        frame.map_offset.y + scr_pos.y,
+                        RGB::named(MIDNIGHTBLUE),
+                    );
+                    ctx.set_active_console(ENTITY_LAYER);
                 }
+            }
+        }
 
-                let objs = state.ecs().entities();
-                let positions = state.ecs().read_storage::<comp::Pos>();
-                let bodies = state.ecs().read_storage::<comp::Body>();
-
-                for o in objs.join() {
-                    let pos = positions.get(o);
-                    let body = bodies.get(o);
-
-                    if pos.is_some() && body.is_some() {
-                        let scr_pos = to_screen_pos(Vec2::from(pos.unwrap().0), self.zoom_level);
-                        let (character, color) = match body.unwrap() {
-                            Body::Humanoid(humanoid) => match humanoid.species {
-                                humanoid::Species::Danari => ('☻', RGB::named(BROWN2)),
-                                humanoid::Species::Dwarf => ('☺', RGB::named(ORANGE)),
-                                humanoid::Species::Elf => ('☺', RGB::named(BLUE)),
-                                humanoid::Species::Human => ('☺', RGB::named(IVORY)),
-                                humanoid::Species::Orc => ('☻', RGB::named(GREEN)),
-                                humanoid::Species::Undead => ('☻', RGB::named(WHITE)),
-                            },
-                            Body::QuadrupedLow(_) => ('4', RGB::named(RED)),
-                            Body::QuadrupedSmall(_) => ('q', RGB::named(RED)),
-                            Body::QuadrupedMedium(_) => ('Q', RGB::named(RED)),
-                            Body::BirdMedium(_) => ('b', RGB::named(RED)),
-                            Body::BirdLarge(_) => ('B', RGB::named(RED)),
-                            Body::FishSmall(_) => ('f', RGB::named(RED)),
-                            Body::FishMedium(_) => ('F', RGB::named(RED)),
-                            Body::BipedLarge(_) => ('2', RGB::named(RED)),
-                            Body::BipedSmall(_) => ('2', RGB::named(RED)),
-                            Body::Object(_) => ('◙', RGB::named(YELLOW)),
-                            Body::Golem(_) => ('G', RGB::named(TAN)),
-                            Body::Dragon(_) => ('₧', RGB::named(RED)),
-                            Body::Theropod(_) => ('T', RGB::named(RED)),
-                            Body::Ship(_) => ('S', RGB::named(BROWN1)),
-                        };
+        for o in objs.join() {
+            let pos = positions.get(o);
+            let body = bodies.get(o);
 
-                        if scr_pos
-                            .map2(screen_size, |e, sz| e >= 0 && e < sz as i32)
-                            .reduce_and()
-                        {
-                            ctx.print_color(
-                                scr_pos.x,
-                                scr_pos.y,
-                                color,
-                                RGB::named(BLACK),
-                                character,
-                            );
-                        }
+            if pos.is_some() && body.is_some() {
+                let scr_pos = frame.to_screen_pos(Vec2::from(pos.unwrap().0), self.zoom_level);
+                let (character, color) = render::body_glyph(body.unwrap());
+                // Objects and ships have no relation and keep their own color
+                let color = match self.settings.entity_colors {
+                    EntityColors::Species => color,
+                    EntityColors::Threat => relation(o).map_or(color, Relation::color),
+                };
+
+                let name = stats
+                    .get(o)
+                    .map_or_else(|| "?".to_string(), |stats| stats.name.clone());
+                if frame.mouse_on_map && scr_pos == frame.mouse_pos {
+                    hovered = Some(name.clone());
+                }
+                if o != client.entity() {
+                    nearby.push((
+                        character,
+                        color,
+                        name,
+                        pos.unwrap().0.distance(frame.player_pos),
+                        Some(o) == self.target,
+                    ));
+                }
+
+                if frame.on_map(scr_pos) {
+                    ctx.print_color(
+                        frame.map_offset.x + scr_pos.x,
+                        frame.map_offset.y + scr_pos.y,
+                        lights.apply(scr_pos, color),
+                        RGB::named(BLACK),
+                        character,
+                    );
+                    // The entity layer has no background, so mark the target and the
+                    // player below it
+                    let highlight = if Some(o) == self.target {
+                        Some(RGB::named(DARKRED))
+                    } else if o == client.entity() {
+                        Some(RGB::named(NAVY))
+                    } else {
+                        None
+                    };
+                    if let Some(bg) = highlight {
+                        ctx.set_active_console(TERRAIN_LAYER);
+                        ctx.set_bg(
+                            frame.map_offset.x + scr_pos.x,
+                            frame.map_offset.y + scr_pos.y,
+                            bg,
+                        );
+                        ctx.set_active_console(ENTITY_LAYER);
                     }
                 }
             }
+        }
+
+        nearby.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap_or(std::cmp::Ordering::Equal));
 
-            ctx.printer(
-                58,
-                screen_size.y - 20,
-                "/------- Controls ------\\",
-                TextAlign::Right,
-                None,
+        ctx.set_active_console(HUD_LAYER);
+        if let Some(rect) = frame.layout.get_on(PanelKind::Entities, frame.hud_size) {
+            hud::draw_entities(ctx, rect, &nearby);
+        }
+
+        if let Some(rect) = frame.layout.get_on(PanelKind::Minimap, frame.hud_size) {
+            let uids = state.ecs().read_resource::<UidAllocator>();
+            let mut markers: Vec<_> = self
+                .waypoints
+                .list()
+                .iter()
+                .map(|waypoint| (waypoint.center(), '♦', RGB::named(MAGENTA)))
+                .collect();
+            markers.extend(
+                client
+                    .group_members()
+                    .keys()
+                    .filter_map(|uid| uids.retrieve_entity_internal(uid.id()))
+                    .filter(|entity| *entity != client.entity())
+                    .filter_map(|entity| positions.get(entity))
+                    .map(|pos| (Vec2::from(pos.0), '☻', RGB::named(CYAN))),
             );
+            let facing = client
+                .current::<comp::Ori>()
+                .map_or('@', |ori| render::facing_glyph(ori.look_vec().into()));
+            markers.push((Vec2::from(frame.player_pos), facing, RGB::named(YELLOW)));
 
-            ctx.printer(
-                58,
-                screen_size.y - 19,
-                "|  wasd/click - Move    |",
-                TextAlign::Right,
-                None,
+            // Loaded cells summed up like the zoomed out map, the rest as remembered
+            let heightmap = &mut self.heightmap;
+            let memory = &self.memory;
+            let z = start.z;
+            minimap::draw(
+                ctx,
+                rect,
+                Vec2::from(frame.player_pos),
+                |wpos| {
+                    if terrain.get_key(TerrainGrid::chunk_key(wpos)).is_some() {
+                        let cell = render::scan_cell(
+                            Vec3::new(wpos.x, wpos.y, z),
+                            minimap::SCALE,
+                            |wpos| heightmap.get(&terrain, wpos),
+                        );
+                        cell.kind.map(|_| (cell.glyph, cell.col))
+                    } else {
+                        memory
+                            .get(wpos)
+                            .map(|remembered| (remembered.glyph, remembered.faded()))
+                    }
+                },
+                &markers,
+            );
+        }
+
+        // Name whatever is under the pointer, an entity before the block it stands on
+        if frame.mouse_on_map && playing && self.character_sheet.is_none() {
+            let cell = &cells[frame.mouse_pos.y as usize * frame.screen_size.x as usize
+                + frame.mouse_pos.x as usize];
+            let tooltip = hovered.unwrap_or_else(|| match (cell.sprite, cell.kind) {
+                (Some(sprite), _) => format!("{:?}", sprite),
+                (None, Some(kind)) => format!("{:?}", kind),
+                (None, None) => "Unknown".to_string(),
+            });
+            let (x, y) = ctx.mouse_pos();
+            let width = tooltip.chars().count() as i32;
+            ctx.print_color(
+                (x + 1).min(frame.hud_size.x - width).max(0),
+                y,
+                RGB::named(WHITE),
+                RGB::named(DARKSLATEGRAY),
+                &hud::fit(&tooltip, frame.hud_size.x),
+            );
+        }
+    }
+
+    /// Draw the HUD panels and whichever screen is open on top of the map
+    fn draw_hud(&mut self, ctx: &mut BTerm, frame: &Frame) {
+        let client = &self.client;
+        // Get Health and Energy
+        let (current_health, max_health) = client
+            .current::<comp::Health>()
+            .map_or((0.0, 0.0), |health| (health.current(), health.maximum()));
+        let (current_energy, max_energy) = client
+            .current::<comp::Energy>()
+            .map_or((0.0, 0.0), |energy| (energy.current(), energy.maximum()));
+        let (current_poise, max_poise) =
+            client.current::<comp::Poise>().map_or((0.0, 0.0), |poise| {
+                (poise.current() as f32, poise.maximum() as f32)
+            });
+        let inventory_storage = client.state().ecs().read_storage::<comp::Inventory>();
+        let inventory = inventory_storage.get(client.entity());
+
+        ctx.set_active_console(HUD_LAYER);
+        // Way to the tracked waypoint along the top of the map
+        if let (Some(rect), Some(waypoint)) = (
+            frame.layout.get_on(PanelKind::Map, frame.hud_size),
+            self.waypoints.tracked(),
+        ) {
+            ctx.print_color(
+                rect.x1 + 1,
+                rect.y1,
+                RGB::named(MAGENTA),
+                RGB::named(BLACK),
+                &hud::fit(
+                    &format!(
+                        "♦ {}: {}",
+                        waypoint.name,
+                        waypoint.describe(frame.player_pos)
+                    ),
+                    rect.width() - 2,
+                ),
             );
-            let clear = "                                                                ";
-            for (i, msg) in self.chat_log.iter().rev().take(10).enumerate() {
-                ctx.printer(
-                    58,
-                    screen_size.y - 12 - i as u16,
-                    clear,
-                    TextAlign::Right,
-                    None,
+        }
+
+        if let Some(rect) = frame.layout.get_on(PanelKind::Chat, frame.hud_size) {
+            hud::draw_chat(ctx, rect, &self.chat_log);
+        }
+
+        if let Some(rect) = frame.layout.get_on(PanelKind::Status, frame.hud_size) {
+            if hud::draw_panel(ctx, rect, "Status") {
+                // Blink the health bar for a moment after taking damage
+                let health_col = match self.damaged_at.map(|t| t.elapsed().as_millis()) {
+                    Some(ms) if ms < 600 && (ms / 100) % 2 == 0 => RGB::named(WHITE),
+                    _ => RGB::named(RED3),
+                };
+                let bars = [
+                    ("HP ", (current_health, max_health), health_col),
+                    ("EN ", (current_energy, max_energy), RGB::named(GREEN4)),
+                    ("PO ", (current_poise, max_poise), RGB::named(GOLD3)),
+                ];
+                for (i, (label, values, col)) in bars.iter().enumerate() {
+                    hud::draw_bar(
+                        ctx,
+                        rect.x1 + 1,
+                        rect.y1 + 1 + i as i32,
+                        rect.width() - 2,
+                        label,
+                        (values.0 / 10.0, values.1 / 10.0),
+                        *col,
+                    );
+                }
+
+                let lines = [
+                    format!("#[pink]FPS: #[]{}", ctx.fps),
+                    format!("#[pink]Frame Time: #[]{} ms", ctx.frame_time_ms),
+                    format!(
+                        "#[pink]Zoom: #[]{:+} #[](1:{:.1})",
+                        self.zoom_level.log(1.5).round() as i32,
+                        self.zoom_level
+                    ),
+                    "#[grey]wasd/click - Move".to_string(),
+                ];
+                hud::draw_lines(
+                    ctx,
+                    Rect::with_exact(rect.x1, rect.y1 + bars.len() as i32, rect.x2, rect.y2),
+                    &lines,
                 );
-                ctx.printer(
-                    58,
-                    screen_size.y - 12 - i as u16,
-                    &format!("#[pink]#[]{}", msg.get(0..48).unwrap_or(&msg)),
-                    TextAlign::Right,
-                    None,
+            }
+        }
+
+        // Where we are and which way we face, to tell others
+        if let Some(rect) = frame.layout.get_on(PanelKind::Compass, frame.hud_size) {
+            if hud::draw_panel(ctx, rect, "Compass") {
+                let chunk = TerrainGrid::chunk_key(frame.player_pos.map(|e| e.floor() as i32));
+                let facing = client.current::<comp::Ori>().map_or_else(
+                    || "?".to_string(),
+                    |ori| {
+                        let dir = Vec2::<f32>::from(ori.look_vec());
+                        let degrees = dir.x.atan2(dir.y).to_degrees().rem_euclid(360.0);
+                        format!("{} ({:.0}°)", render::compass_point(dir), degrees)
+                    },
                 );
+                let site = client
+                    .sites()
+                    .values()
+                    .map(|site| &site.site)
+                    .filter(|site| world_map::is_landmark(&site.kind))
+                    .min_by_key(|site| {
+                        (site.wpos - frame.player_pos.xy().map(|e| e as i32))
+                            .map(|e| e as i64 * e as i64)
+                            .sum()
+                    })
+                    .map_or_else(|| "?".to_string(), world_map::site_name);
+                let lines = [
+                    format!(
+                        "#[pink]Pos: #[]{:.0}, {:.0}",
+                        frame.player_pos.x, frame.player_pos.y
+                    ),
+                    format!("#[pink]Alt: #[]{:.0}", frame.player_pos.z),
+                    format!("#[pink]Chunk: #[]{}, {}", chunk.x, chunk.y),
+                    format!("#[pink]Facing: #[]{}", facing),
+                    format!("#[pink]Near: #[]{}", hud::fit(&site, rect.width() - 8)),
+                ];
+                hud::draw_lines(ctx, rect, &lines);
             }
+        }
 
-            ctx.draw_box(39, 0, 20, 5, RGB::named(WHITE), RGB::named(BLACK));
-            ctx.printer(
-                58,
-                1,
-                &format!("#[pink]FPS: #[]{}", ctx.fps),
-                TextAlign::Right,
-                None,
+        if let (Some(rect), Some(buffs)) = (
+            frame.layout.get_on(PanelKind::Effects, frame.hud_size),
+            client.current::<comp::Buffs>(),
+        ) {
+            let effects: Vec<_> = buffs
+                .iter_active()
+                .map(|buff| {
+                    let stacks = buffs.kinds.get(&buff.kind).map_or(1, |ids| ids.len());
+                    (buff, stacks)
+                })
+                .collect();
+            hud::draw_effects(ctx, rect, &effects);
+        }
+
+        if let (Some(rect), Some(inventory)) = (
+            frame.layout.get_on(PanelKind::Hotbar, frame.hud_size),
+            inventory,
+        ) {
+            let skill_set = client.current::<comp::SkillSet>();
+            let busy = client
+                .current::<comp::CharacterState>()
+                .map_or(false, |state| state.is_attack());
+            hotbar::draw(
+                ctx,
+                rect,
+                &hotbar::slots(inventory, skill_set.as_ref(), &self.settings.keymap),
+                current_energy,
+                busy,
             );
-            ctx.printer(
-                58,
-                2,
-                &format!("#[pink]Frame Time: #[]{} ms", ctx.frame_time_ms),
-                TextAlign::Right,
-                None,
+        }
+
+        if self.inv_toggle {
+            if let (Some(rect), Some(inventory)) = (
+                frame.layout.get_on(PanelKind::Inventory, frame.hud_size),
+                inventory,
+            ) {
+                let slots: Vec<_> = inventory
+                    .slots_with_id()
+                    .filter_map(|(id, slot)| slot.as_ref().map(|item| (id, item)))
+                    .collect();
+                let cursor = (self.invpos as usize).min(slots.len().saturating_sub(1));
+                self.arrowed = slots.get(cursor).map(|(id, _)| Slot::Inventory(*id));
+                let items: Vec<_> = slots
+                    .iter()
+                    .map(|(_, item)| {
+                        if item.amount() > 1 {
+                            format!("{} x{}", item.name(), item.amount())
+                        } else {
+                            item.name().to_string()
+                        }
+                    })
+                    .collect();
+                hud::draw_inventory(ctx, rect, &items, cursor);
+            }
+        }
+
+        if let (Some(sheet), Some(skill_set)) = (
+            self.character_sheet.as_ref(),
+            client.current::<comp::SkillSet>(),
+        ) {
+            sheet.draw(
+                ctx,
+                Rect::with_size(0, 0, frame.hud_size.x, frame.hud_size.y),
+                client.current::<comp::Body>(),
+                &skill_set,
+                &self.settings.keymap,
             );
-            ctx.printer(
-                58,
-                3,
-                &format!(
-                    "#[pink]Health: #[]{}/#[]{}",
-                    current_health / 10.0,
-                    max_health / 10.0
-                ),
-                TextAlign::Right,
-                None,
+        }
+
+        if let Some(map) = self.world_map.as_mut() {
+            let world = client.world_data();
+            let sites: Vec<_> = client.sites().values().map(|site| &site.site).collect();
+            let facing = client
+                .current::<comp::Ori>()
+                .map_or('@', |ori| render::facing_glyph(ori.look_vec().into()));
+            let mut markers: Vec<_> = self
+                .waypoints
+                .list()
+                .iter()
+                .map(|waypoint| (waypoint.center(), '♦', RGB::named(MAGENTA)))
+                .collect();
+            markers.push((Vec2::from(frame.player_pos), facing, RGB::named(YELLOW)));
+            map.draw(
+                ctx,
+                Rect::with_size(0, 0, frame.hud_size.x, frame.hud_size.y),
+                &world.lod_base,
+                &world.lod_alt,
+                &sites,
+                &markers,
+                &self.settings.keymap,
             );
-            ctx.printer(
-                58,
-                4,
-                &format!(
-                    "#[pink]Energy: #[]{}/#[]{}",
-                    current_energy / 10.0,
-                    max_energy / 10.0
-                ),
-                TextAlign::Right,
-                None,
+        }
+
+        if let Some(screen) = self.waypoint_screen.as_ref() {
+            screen.draw(
+                ctx,
+                Rect::with_size(0, 0, frame.hud_size.x, frame.hud_size.y),
+                &self.waypoints,
+                frame.player_pos,
+                &self.settings.keymap,
+            );
+        }
+
+        if let Some(screen) = self.keybinding_screen.as_ref() {
+            screen.draw(
+                ctx,
+                Rect::with_size(0, 0, frame.hud_size.x, frame.hud_size.y),
+                &self.settings.keymap,
             );
         }
-        client.cleanup();
+    }
+}
+
+impl GameState for State {
+    fn tick(&mut self, ctx: &mut BTerm) {
+        for layer in &[TERRAIN_LAYER, ENTITY_LAYER, HUD_LAYER] {
+            ctx.set_active_console(*layer);
+            ctx.cls();
+        }
+
+        // Note damage taken since the last frame, which stops exploring
+        let current_health = self
+            .client
+            .current::<comp::Health>()
+            .map_or(0.0, |health| health.current());
+        let took_damage = current_health < self.last_health;
+        if took_damage {
+            self.damaged_at = Some(Instant::now());
+        }
+        self.last_health = current_health;
+
+        // Invite Logic
+        let (inviter_uid, invite_kind) =
+            if let Some((inviter_uid, _, _, invite_kind)) = self.client.invite() {
+                (Some(inviter_uid), Some(invite_kind))
+            } else {
+                (None, None)
+            };
+
+        //Get entity username from UID
+        let inviter_username = if let Some(uid) = inviter_uid {
+            if let Some(entity) = self
+                .client
+                .state()
+                .ecs()
+                .read_resource::<UidAllocator>()
+                .retrieve_entity_internal(uid.id())
+            {
+                if let Some(player) = self
+                    .client
+                    .state()
+                    .read_storage::<comp::Player>()
+                    .get(entity)
+                {
+                    player.alias.clone()
+                } else {
+                    "".to_string()
+                }
+            } else {
+                "".to_string()
+            }
+        } else {
+            "".to_string()
+        };
+
+        let frame = Frame::new(ctx, &self.settings.layout, &self.client);
+        let inputs = self.handle_input(ctx, &frame);
+        let events = self.client.tick(inputs, self.clock.dt(), |_| ()).unwrap();
+        self.handle_events(events);
+        let (wposes, cells) = self.scan_map(ctx, &frame, took_damage);
+        self.draw_map(ctx, &frame, &wposes, &cells);
+        self.draw_hud(ctx, &frame);
+
+        self.client.cleanup();
         // Wait for next tick
-        clock.tick();
+        self.clock.tick();
     }
 }
 
@@ -535,6 +1416,16 @@ fn main() -> BError {
     let password = matches.value_of("password").unwrap_or("");
     let character_name = matches.value_of("character").unwrap_or("");
 
+    let settings_path = matches.value_of("settings").unwrap_or("velobracket.ron");
+    let mut settings = Settings::load(Path::new(settings_path));
+    if let Some(width) = matches.value_of("width").and_then(|w| w.parse().ok()) {
+        settings.width = width;
+    }
+    if let Some(height) = matches.value_of("height").and_then(|h| h.parse().ok()) {
+        settings.height = height;
+    }
+    settings.validate();
+
     // Parse server socket
     let mut server_spec = format!("{}:{}", server_addr, server_port);
     let mut server_spec2 = server_spec.clone();
@@ -601,13 +1492,62 @@ fn main() -> BError {
 
     client.set_view_distance(view_distance);
 
-    let context = BTermBuilder::simple80x50()
+    // Terrain and entities share the map font, the HUD may use its own. Every console is
+    // sized to fill the same window with its font.
+    let (hud_font_w, hud_font_h) = settings.hud_font_size;
+    let mut builder = BTermBuilder::new()
+        .with_dimensions(settings.width, settings.height)
+        .with_tile_dimensions(8, 8)
         .with_title(&format!("velobracket - {}", character_name))
+        .with_font("terminal8x8.png", 8, 8);
+    if settings.hud_font != "terminal8x8.png" {
+        builder = builder.with_font(&settings.hud_font, hud_font_w, hud_font_h);
+    }
+    let context = builder
+        .with_advanced_input(true)
+        .with_simple_console(settings.width, settings.height, "terminal8x8.png")
+        .with_sparse_console_no_bg(settings.width, settings.height, "terminal8x8.png")
+        .with_sparse_console(
+            settings.width * 8 / hud_font_w,
+            settings.height * 8 / hud_font_h,
+            &settings.hud_font,
+        )
+        .with_automatic_console_resize(true)
         .build()?;
 
-    let mut gs = State {
-        ecs: World::new(),
+    // Colors used by `#[name]` markup in HUD text
+    register_palette_color("pink", RGB::named(PINK));
+    register_palette_color("grey", RGB::named(GREY));
+
+    let server = format!("{}:{}", server_addr, server_port);
+    let memory = MapMemory::load(memory::path(
+        Path::new(&settings.data_dir),
+        &server,
+        character_name,
+    ));
+    let waypoints = Waypoints::load(waypoints::path(Path::new(&settings.data_dir), &server));
+
+    let gs = State {
+        client,
+        clock,
+        settings,
+        settings_path: PathBuf::from(settings_path),
         zoom_level: 1.0,
+        heightmap: HeightmapCache::new(),
+        run_benchmark: false,
+        benchmark_and_quit: matches.is_present("benchmark"),
+        find_resource: false,
+        memory,
+        waypoints,
+        exploring: false,
+        last_health: 0.0,
+        damaged_at: None,
+        character_sheet: None,
+        keybinding_screen: None,
+        world_map: None,
+        waypoint_screen: None,
+        held_keys: HeldKeys::default(),
+        is_ability_active: [false; hotbar::ABILITY_SLOTS],
         chat_log: Vec::<String>::new(),
         chat_input: String::new(),
         chat_input_enabled: false,
@@ -624,9 +1564,9 @@ fn main() -> BError {
         arrowedpos: 0,
         swap: false,
         use_item: false,
+        travel: None,
+        target: None,
     };
 
-    gs.ecs.insert(client);
-    gs.ecs.insert(clock);
     main_loop(context, gs)
 }
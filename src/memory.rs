@@ -0,0 +1,256 @@
+use bracket_terminal::prelude::*;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use vek::*;
+
+use veloren_common::{
+    terrain::{TerrainChunkSize, TerrainGrid},
+    vol::RectVolSize,
+};
+
+use crate::{heightmap, render::Cell};
+
+/// Start of every map memory file, ending in the format version
+const MAGIC: &[u8] = b"VBMEM1";
+
+/// Top glyph and color of a column when it was last seen
+#[derive(Clone, Copy, PartialEq)]
+pub struct Remembered {
+    pub glyph: char,
+    pub col: (u8, u8, u8),
+}
+
+impl Remembered {
+    /// Dim grey of the remembered color's brightness, to tell it apart from what's in view
+    pub fn faded(&self) -> RGB {
+        let (r, g, b) = self.col;
+        let grey = (r as f32 + g as f32 + b as f32) / (3.0 * 255.0);
+        let grey = 0.15 + grey * 0.3;
+        RGB::from_f32(grey, grey, grey)
+    }
+}
+
+/// Every column seen on the map, kept between sessions in a file per server and character
+pub struct MapMemory {
+    path: PathBuf,
+    chunks: HashMap<Vec2<i32>, Vec<Option<Remembered>>>,
+    /// Whether anything changed since the last save
+    dirty: bool,
+    saved_at: Instant,
+}
+
+/// File the memory of `character` on `server` is kept in, under `data_dir`
+pub fn path(data_dir: &Path, server: &str, character: &str) -> PathBuf {
+    data_dir.join(format!(
+        "{}.map",
+        file_name(&format!("{}_{}", server, character))
+    ))
+}
+
+/// `name` with everything but letters and digits replaced, safe to use as a file name
+pub fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+impl MapMemory {
+    /// Read the memory from `path`, starting empty if there's no file yet
+    pub fn load(path: PathBuf) -> Self {
+        let mut memory = Self {
+            path,
+            chunks: HashMap::new(),
+            dirty: false,
+            saved_at: Instant::now(),
+        };
+        if let Ok(bytes) = fs::read(&memory.path) {
+            match decode(&bytes) {
+                Some(chunks) => memory.chunks = chunks,
+                None => println!(
+                    "Failed to read map memory from {}, starting over",
+                    memory.path.display()
+                ),
+            }
+        }
+        memory
+    }
+
+    /// Write the memory out, if anything changed since it was last written
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        if let Some(dir) = self.path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                println!("Failed to create {}: {}", dir.display(), err);
+                return;
+            }
+        }
+        match fs::write(&self.path, encode(&self.chunks)) {
+            Ok(()) => self.dirty = false,
+            Err(err) => println!("Failed to save {}: {}", self.path.display(), err),
+        }
+        self.saved_at = Instant::now();
+    }
+
+    /// Save if it's been at least `interval` since the last save
+    pub fn save_every(&mut self, interval: Duration) {
+        if self.saved_at.elapsed() >= interval {
+            self.save();
+        }
+    }
+
+    /// Remember how a column looks now, returns whether it had never been seen before
+    pub fn remember(&mut self, wpos: Vec2<i32>, cell: &Cell) -> bool {
+        let remembered = Remembered {
+            glyph: cell.glyph,
+            col: (
+                (cell.col.r * 255.0) as u8,
+                (cell.col.g * 255.0) as u8,
+                (cell.col.b * 255.0) as u8,
+            ),
+        };
+        let columns = self
+            .chunks
+            .entry(TerrainGrid::chunk_key(wpos))
+            .or_insert_with(|| vec![None; chunk_columns()]);
+        let column = &mut columns[heightmap::column_index(wpos.into())];
+        let new = column.is_none();
+        if *column != Some(remembered) {
+            *column = Some(remembered);
+            self.dirty = true;
+        }
+        new
+    }
+
+    pub fn get(&self, wpos: Vec2<i32>) -> Option<Remembered> {
+        self.chunks
+            .get(&TerrainGrid::chunk_key(wpos))
+            .and_then(|columns| columns[heightmap::column_index(wpos.into())])
+    }
+}
+
+/// Whatever was seen since the last save is written out when the game closes
+impl Drop for MapMemory {
+    fn drop(&mut self) {
+        self.save();
+    }
+}
+
+fn chunk_columns() -> usize {
+    (TerrainChunkSize::RECT_SIZE.x * TerrainChunkSize::RECT_SIZE.y) as usize
+}
+
+/// Each chunk is its key and the number of columns seen, followed by every seen column as its
+/// index in the chunk, glyph and color
+fn encode(chunks: &HashMap<Vec2<i32>, Vec<Option<Remembered>>>) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    for (key, columns) in chunks {
+        let seen: Vec<_> = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, column)| column.map(|column| (i, column)))
+            .collect();
+        bytes.extend(&key.x.to_le_bytes());
+        bytes.extend(&key.y.to_le_bytes());
+        bytes.extend(&(seen.len() as u16).to_le_bytes());
+        for (i, column) in seen {
+            bytes.extend(&(i as u16).to_le_bytes());
+            bytes.extend(&(column.glyph as u32).to_le_bytes());
+            bytes.extend(&[column.col.0, column.col.1, column.col.2]);
+        }
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<HashMap<Vec2<i32>, Vec<Option<Remembered>>>> {
+    let mut bytes = bytes.strip_prefix(MAGIC)?;
+    let mut chunks = HashMap::new();
+    while !bytes.is_empty() {
+        let key = take(&mut bytes, 8)?;
+        let key = Vec2::new(
+            i32::from_le_bytes(key[0..4].try_into().ok()?),
+            i32::from_le_bytes(key[4..8].try_into().ok()?),
+        );
+        let count = u16::from_le_bytes(take(&mut bytes, 2)?.try_into().ok()?);
+        let mut columns = vec![None; chunk_columns()];
+        for _ in 0..count {
+            let column = take(&mut bytes, 9)?;
+            let i = u16::from_le_bytes(column[0..2].try_into().ok()?) as usize;
+            let glyph = std::char::from_u32(u32::from_le_bytes(column[2..6].try_into().ok()?))?;
+            *columns.get_mut(i)? = Some(Remembered {
+                glyph,
+                col: (column[6], column[7], column[8]),
+            });
+        }
+        chunks.insert(key, columns);
+    }
+    Some(chunks)
+}
+
+/// Split `n` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None;
+    }
+    let (taken, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Some(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> HashMap<Vec2<i32>, Vec<Option<Remembered>>> {
+        let mut chunks = HashMap::new();
+        let mut columns = vec![None; chunk_columns()];
+        columns[0] = Some(Remembered {
+            glyph: '.',
+            col: (10, 200, 30),
+        });
+        columns[chunk_columns() - 1] = Some(Remembered {
+            glyph: '≈',
+            col: (0, 0, 255),
+        });
+        chunks.insert(Vec2::new(-3, 7), columns);
+        chunks.insert(Vec2::new(0, 0), vec![None; chunk_columns()]);
+        chunks
+    }
+
+    #[test]
+    fn memory_reads_back_what_was_written() {
+        let chunks = sample();
+        let decoded = decode(&encode(&chunks)).unwrap();
+        assert_eq!(decoded.len(), chunks.len());
+        for (key, columns) in &chunks {
+            assert!(decoded[key] == *columns);
+        }
+    }
+
+    #[test]
+    fn broken_memory_is_not_read() {
+        let bytes = encode(&sample());
+        assert!(decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(decode(b"VBMEM0").is_none());
+        assert!(decode(&[]).is_none());
+        assert!(decode(MAGIC).unwrap().is_empty());
+    }
+
+    #[test]
+    fn file_names_keep_only_letters_and_digits() {
+        assert_eq!(
+            file_name("server.veloren.net:14004"),
+            "server_veloren_net_14004"
+        );
+        assert_eq!(
+            path(Path::new("data"), "host:1", "Bob"),
+            Path::new("data").join("host_1_Bob.map")
+        );
+    }
+}
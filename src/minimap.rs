@@ -0,0 +1,49 @@
+use bracket_terminal::prelude::*;
+use vek::*;
+
+use crate::hud;
+
+/// Blocks across each minimap cell
+pub const SCALE: f32 = 8.0;
+
+/// Shrunk down map around `center`, with `markers` such as the player and group members on top
+///
+/// `cell` gives the glyph and color of the `SCALE` blocks across whose top left corner is at a
+/// world position, if anything is known about them.
+pub fn draw(
+    ctx: &mut BTerm,
+    rect: Rect,
+    center: Vec2<f32>,
+    mut cell: impl FnMut(Vec2<i32>) -> Option<(char, RGB)>,
+    markers: &[(Vec2<f32>, char, RGB)],
+) {
+    if !hud::draw_panel(ctx, rect, "Minimap") {
+        return;
+    }
+    let size = Vec2::new(rect.width() - 2, rect.height() - 2);
+    let origin = Vec2::new(rect.x1 + 1, rect.y1 + 1);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let offs = (Vec2::new(x, y) - size / 2).map(|e| e as f32) * Vec2::new(1.0, -1.0);
+            let wpos = (center + offs * SCALE).map(|e| e.floor() as i32);
+            if let Some((glyph, col)) = cell(wpos) {
+                ctx.print_color(origin.x + x, origin.y + y, col, RGB::named(BLACK), glyph);
+            }
+        }
+    }
+
+    for (pos, glyph, col) in markers {
+        let cell =
+            ((*pos - center) / SCALE * Vec2::new(1.0, -1.0)).map(|e| e.floor() as i32) + size / 2;
+        if cell.map2(size, |e, sz| e >= 0 && e < sz).reduce_and() {
+            ctx.print_color(
+                origin.x + cell.x,
+                origin.y + cell.y,
+                *col,
+                RGB::named(BLACK),
+                *glyph,
+            );
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use bracket_lib::prelude::{a_star_search, BaseMap, SmallVec};
+use std::cell::Cell;
+use vek::*;
+
+use veloren_common::{terrain::TerrainGrid, vol::ReadVol};
+
+/// Blocks around the start and goal that a path may wander through
+const MARGIN: i32 = 16;
+/// Farthest a path is searched for, in blocks
+const MAX_DISTANCE: i32 = 256;
+/// Most blocks a path drops down in one step
+const MAX_DROP: i32 = 3;
+/// Extra cost of stepping up a block
+const STEP_UP_COST: f32 = 1.0;
+/// Cost of moving through water compared to walking
+const SWIM_COST: f32 = 2.0;
+/// Most blocks a search looks around from before giving up, so it fits in a frame
+const MAX_EXPANDED: usize = 20_000;
+
+fn is_solid(terrain: &TerrainGrid, wpos: Vec3<i32>) -> bool {
+    // Unloaded terrain counts as solid so paths stay where we can see
+    terrain.get(wpos).map_or(true, |block| block.is_solid())
+}
+
+fn is_water(terrain: &TerrainGrid, wpos: Vec3<i32>) -> bool {
+    terrain.get(wpos).map_or(false, |block| block.is_liquid())
+}
+
+/// Whether a character fits at `wpos`, either standing on solid ground or swimming if `swim`
+fn is_walkable(terrain: &TerrainGrid, wpos: Vec3<i32>, swim: bool) -> bool {
+    !is_solid(terrain, wpos)
+        && !is_solid(terrain, wpos + Vec3::unit_z())
+        && if is_water(terrain, wpos) {
+            swim
+        } else {
+            is_solid(terrain, wpos - Vec3::unit_z())
+        }
+}
+
+/// A box of terrain, with every block in it numbered for `a_star_search`
+struct TerrainNav<'a> {
+    terrain: &'a TerrainGrid,
+    min: Vec3<i32>,
+    size: Vec3<i32>,
+    swim: bool,
+    /// Blocks looked around from so far
+    expanded: Cell<usize>,
+}
+
+impl<'a> TerrainNav<'a> {
+    fn index(&self, wpos: Vec3<i32>) -> Option<usize> {
+        let pos = wpos - self.min;
+        if pos.map2(self.size, |e, sz| e >= 0 && e < sz).reduce_and() {
+            Some(((pos.z * self.size.y + pos.y) * self.size.x + pos.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn wpos(&self, index: usize) -> Vec3<i32> {
+        let index = index as i32;
+        self.min
+            + Vec3::new(
+                index % self.size.x,
+                index / self.size.x % self.size.y,
+                index / (self.size.x * self.size.y),
+            )
+    }
+}
+
+impl<'a> BaseMap for TerrainNav<'a> {
+    fn get_available_exits(&self, index: usize) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
+        // Without exits left the search runs dry and fails
+        if self.expanded.get() >= MAX_EXPANDED {
+            return exits;
+        }
+        self.expanded.set(self.expanded.get() + 1);
+        let wpos = self.wpos(index);
+        let swimming = is_water(self.terrain, wpos);
+
+        for dir in &[
+            Vec2::new(0, 1),
+            Vec2::new(1, 0),
+            Vec2::new(0, -1),
+            Vec2::new(-1, 0),
+            Vec2::new(1, 1),
+            Vec2::new(1, -1),
+            Vec2::new(-1, 1),
+            Vec2::new(-1, -1),
+        ] {
+            // Don't cut corners past walls
+            if dir.x != 0 && dir.y != 0 {
+                let blocked = |offs: Vec2<i32>| {
+                    let side = wpos + Vec3::from(offs);
+                    is_solid(self.terrain, side) || is_solid(self.terrain, side + Vec3::unit_z())
+                };
+                if blocked(Vec2::new(dir.x, 0)) || blocked(Vec2::new(0, dir.y)) {
+                    continue;
+                }
+            }
+
+            // Stay level if possible, otherwise step up or drop down
+            let step_up = !is_solid(self.terrain, wpos + Vec3::unit_z() * 2);
+            for dz in [0, 1].iter().copied().chain((1..=MAX_DROP).map(|d| -d)) {
+                let next = wpos + Vec3::new(dir.x, dir.y, dz);
+                if (dz == 1 && !step_up) || !is_walkable(self.terrain, next, self.swim) {
+                    continue;
+                }
+                if let Some(next_index) = self.index(next) {
+                    let mut cost = dir.map(|e| e as f32).magnitude();
+                    if swimming || is_water(self.terrain, next) {
+                        cost *= SWIM_COST;
+                    }
+                    if dz > 0 {
+                        cost += STEP_UP_COST;
+                    }
+                    exits.push((next_index, cost));
+                }
+                break;
+            }
+        }
+
+        // Swim up and down
+        if swimming {
+            for dz in &[1, -1] {
+                let next = wpos + Vec3::unit_z() * *dz;
+                if is_walkable(self.terrain, next, self.swim) {
+                    if let Some(next_index) = self.index(next) {
+                        exits.push((next_index, SWIM_COST));
+                    }
+                }
+            }
+        }
+
+        exits
+    }
+
+    fn get_pathing_distance(&self, a: usize, b: usize) -> f32 {
+        self.wpos(a)
+            .map(|e| e as f32)
+            .distance(self.wpos(b).map(|e| e as f32))
+    }
+}
+
+/// Somewhere to stand in the column at `wpos`, as close to its height as possible
+fn landing(terrain: &TerrainGrid, wpos: Vec3<i32>, swim: bool) -> Option<Vec3<i32>> {
+    (0..=MARGIN * 2)
+        .flat_map(|d| vec![wpos + Vec3::unit_z() * d, wpos - Vec3::unit_z() * d])
+        .find(|wpos| is_walkable(terrain, *wpos, swim))
+}
+
+/// Blocks to walk through from `start` to the column at `goal`, not including `start`
+///
+/// Only looks through loaded terrain within `MAX_DISTANCE` blocks, and gives up after looking
+/// around from `MAX_EXPANDED` of them. `goal` only needs to be roughly at the right height, the
+/// path ends wherever there's room to stand closest to it. Water is avoided entirely unless
+/// `swim` is set.
+pub fn find_path(
+    terrain: &TerrainGrid,
+    start: Vec3<i32>,
+    goal: Vec3<i32>,
+    swim: bool,
+) -> Option<Vec<Vec3<i32>>> {
+    if Vec2::from(goal - start).map(|e: i32| e.abs()).reduce_max() > MAX_DISTANCE {
+        return None;
+    }
+    let goal = landing(terrain, goal, swim)?;
+
+    let min = start.map2(goal, |a, b| a.min(b)) - MARGIN;
+    let max = start.map2(goal, |a, b| a.max(b)) + MARGIN;
+    let nav = TerrainNav {
+        terrain,
+        min,
+        size: max - min + 1,
+        swim,
+        expanded: Cell::new(0),
+    };
+
+    let path = a_star_search(nav.index(start)?, nav.index(goal)?, &nav);
+    if path.success {
+        Some(
+            path.steps
+                .into_iter()
+                .skip(1)
+                .map(|i| nav.wpos(i))
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+/// Walking along a path, one block at a time
+pub struct Travel {
+    path: Vec<Vec3<i32>>,
+    next: usize,
+}
+
+impl Travel {
+    pub fn new(path: Vec<Vec3<i32>>) -> Self {
+        Self { path, next: 0 }
+    }
+
+    /// Where the path ends
+    pub fn destination(&self) -> Option<Vec3<i32>> {
+        self.path.last().copied()
+    }
+
+    /// Blocks still ahead
+    pub fn remaining(&self) -> &[Vec3<i32>] {
+        &self.path[self.next.min(self.path.len())..]
+    }
+
+    /// Direction to walk in from `pos` to keep following the path, none once there
+    pub fn move_dir(&mut self, pos: Vec3<f32>) -> Option<Vec2<f32>> {
+        while let Some(block) = self.path.get(self.next) {
+            let delta = Vec2::from(block.map(|e| e as f32 + 0.5)) - Vec2::from(pos);
+            if delta.magnitude_squared() > 0.3 * 0.3 {
+                return Some(delta.normalized());
+            }
+            self.next += 1;
+        }
+        None
+    }
+
+    /// Whether the next block is a step up from `pos`, which needs a jump
+    pub fn needs_jump(&self, pos: Vec3<f32>) -> bool {
+        self.path
+            .get(self.next)
+            .map_or(false, |block| block.z > pos.z.floor() as i32)
+    }
+
+    /// Whether `pos` has strayed so far from the path that it should be found again
+    pub fn is_lost(&self, pos: Vec3<f32>) -> bool {
+        self.path.get(self.next).map_or(false, |block| {
+            let delta = block.map(|e| e as f32 + 0.5) - pos;
+            Vec2::from(delta).magnitude() > 3.0 || delta.z.abs() > MAX_DROP as f32 + 1.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heightmap;
+    use veloren_common::{
+        terrain::{Block, BlockKind, SpriteKind},
+        vol::WriteVol,
+    };
+
+    fn rock() -> Block {
+        Block::new(BlockKind::Rock, Rgb::new(100, 100, 100))
+    }
+
+    /// Set the blocks from `min` to `max`, both included
+    fn fill(terrain: &mut TerrainGrid, min: Vec3<i32>, max: Vec3<i32>, block: Block) {
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    terrain.set(Vec3::new(x, y, z), block).unwrap();
+                }
+            }
+        }
+    }
+
+    /// Whether every step of `path` from `start` moves to a neighbouring block
+    fn is_connected(start: Vec3<i32>, path: &[Vec3<i32>]) -> bool {
+        std::iter::once(&start)
+            .chain(path)
+            .zip(path)
+            .all(|(a, b)| (*b - *a).map(|e| e.abs()).reduce_max() <= MAX_DROP)
+    }
+
+    #[test]
+    fn paths_go_around_walls_without_cutting_corners() {
+        let mut terrain = heightmap::tests::flat();
+        fill(
+            &mut terrain,
+            Vec3::new(2, -3, 0),
+            Vec3::new(2, 3, 1),
+            rock(),
+        );
+        let start = Vec3::new(0, 0, 0);
+        let path = find_path(&terrain, start, Vec3::new(4, 0, 0), false).unwrap();
+
+        assert_eq!(path.last(), Some(&Vec3::new(4, 0, 0)));
+        assert!(is_connected(start, &path));
+        for (a, b) in std::iter::once(&start).chain(&path).zip(&path) {
+            assert!(!is_solid(&terrain, *b), "walks into the wall at {:?}", b);
+            let step = *b - *a;
+            for side in &[Vec3::new(step.x, 0, 0), Vec3::new(0, step.y, 0)] {
+                assert!(
+                    !is_solid(&terrain, *a + *side),
+                    "cuts the corner from {:?} to {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn paths_step_up_one_block_but_not_two() {
+        let mut terrain = heightmap::tests::flat();
+        // Wider than the searched area, so there's no way around
+        fill(
+            &mut terrain,
+            Vec3::new(2, -31, 0),
+            Vec3::new(2, 31, 0),
+            rock(),
+        );
+        let start = Vec3::new(0, 0, 0);
+        let goal = Vec3::new(4, 0, 0);
+        let path = find_path(&terrain, start, goal, false).unwrap();
+        assert!(path.iter().any(|wpos| wpos.x == 2 && wpos.z == 1));
+        assert_eq!(path.last(), Some(&goal));
+
+        fill(
+            &mut terrain,
+            Vec3::new(2, -31, 1),
+            Vec3::new(2, 31, 1),
+            rock(),
+        );
+        assert!(find_path(&terrain, start, goal, false).is_none());
+    }
+
+    #[test]
+    fn water_is_only_crossed_when_swimming() {
+        let mut terrain = heightmap::tests::flat();
+        fill(
+            &mut terrain,
+            Vec3::new(2, -3, -2),
+            Vec3::new(3, 3, 0),
+            Block::water(SpriteKind::Empty),
+        );
+        let start = Vec3::new(0, 0, 0);
+        let goal = Vec3::new(5, 0, 0);
+
+        let path = find_path(&terrain, start, goal, false).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().all(|wpos| !is_water(&terrain, *wpos)));
+
+        let path = find_path(&terrain, start, goal, true).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().any(|wpos| is_water(&terrain, *wpos)));
+        assert!(is_connected(start, &path));
+
+        // With no way around, only swimming gets there
+        fill(
+            &mut terrain,
+            Vec3::new(2, -31, -2),
+            Vec3::new(3, 31, 0),
+            Block::water(SpriteKind::Empty),
+        );
+        assert!(find_path(&terrain, start, goal, false).is_none());
+        assert!(find_path(&terrain, start, goal, true).is_some());
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let mut terrain = heightmap::tests::flat();
+        // A walled in pit
+        fill(
+            &mut terrain,
+            Vec3::new(5, -3, 0),
+            Vec3::new(11, 3, 2),
+            rock(),
+        );
+        fill(
+            &mut terrain,
+            Vec3::new(6, -2, 1),
+            Vec3::new(10, 2, 2),
+            Block::air(SpriteKind::Empty),
+        );
+        let start = Vec3::new(0, 0, 0);
+        assert!(find_path(&terrain, start, Vec3::new(8, 0, 1), false).is_none());
+        // Outside the loaded chunks, and too far to look
+        assert!(find_path(&terrain, start, Vec3::new(40, 0, 0), false).is_none());
+        assert!(find_path(&terrain, start, Vec3::new(MAX_DISTANCE + 1, 0, 0), false).is_none());
+    }
+
+    #[test]
+    fn travel_follows_the_path_and_jumps_up_steps() {
+        let mut travel = Travel::new(vec![Vec3::new(1, 0, 0), Vec3::new(2, 0, 1)]);
+        let dir = travel.move_dir(Vec3::new(0.5, 0.5, 0.0)).unwrap();
+        assert!((dir - Vec2::new(1.0, 0.0)).magnitude() < 1e-4);
+        assert!(!travel.needs_jump(Vec3::new(0.5, 0.5, 0.0)));
+
+        // Reaching a block moves on to the next
+        let pos = Vec3::new(1.5, 0.5, 0.0);
+        assert!(travel.move_dir(pos).is_some());
+        assert_eq!(travel.remaining(), &[Vec3::new(2, 0, 1)]);
+        assert!(travel.needs_jump(pos));
+        assert!(!travel.is_lost(pos));
+        assert!(travel.is_lost(Vec3::new(1.5, 6.5, 0.0)));
+
+        assert!(travel.move_dir(Vec3::new(2.5, 0.5, 1.0)).is_none());
+        assert_eq!(travel.destination(), Some(Vec3::new(2, 0, 1)));
+    }
+}
@@ -0,0 +1,476 @@
+use bracket_terminal::prelude::*;
+use serde::{Deserialize, Serialize};
+use vek::*;
+
+use veloren_common::{
+    comp::{agent::Psyche, humanoid, Alignment, Body},
+    terrain::{Block, BlockKind, SpriteKind, TerrainGrid},
+    vol::ReadVol,
+};
+
+/// Brightening applied to blocks found below the player's level, nearest first
+const LEVEL_TINT: [f32; 3] = [0.15, 0.30, 0.50];
+/// How many blocks below the player a column is scanned for
+const ELEVATION: i32 = 16;
+/// How many blocks above the player a column is checked for a roof
+const ROOF_HEIGHT: i32 = 24;
+/// Most columns sampled along each side of a zoomed out cell
+const MAX_SAMPLES: i32 = 4;
+/// Wild creatures at least this aggressive go for players that come near
+const AGGRESSIVE: f32 = 0.5;
+
+/// Topmost visible thing in a single map column
+#[derive(Clone, Copy)]
+pub struct Cell {
+    pub glyph: char,
+    pub col: RGB,
+    /// Light emitted by the visible block or sprite, 0 if none
+    pub glow: u8,
+    /// Whether something solid blocks the sky above this column
+    pub roofed: bool,
+    /// Kind of the topmost filled block, if one was found
+    pub kind: Option<BlockKind>,
+    /// Topmost sprite above that block, if any
+    pub sprite: Option<SpriteKind>,
+}
+
+/// Block holding `sprite` among the columns the map cell at `wpos` stands for at `zoom`, looking
+/// down each column as far as `scan_column` does
+pub fn find_sprite(
+    terrain: &TerrainGrid,
+    wpos: Vec3<i32>,
+    zoom: f32,
+    sprite: SpriteKind,
+) -> Option<Vec3<i32>> {
+    let n = zoom.ceil().max(1.0) as i32;
+    (0..n)
+        .flat_map(|j| (0..n).map(move |i| wpos + Vec3::new(i, -j, 0)))
+        .flat_map(|column| (-2..ELEVATION).map(move |z| column - Vec3::unit_z() * z))
+        .find(|pos| terrain.get(*pos).ok().and_then(|block| block.get_sprite()) == Some(sprite))
+}
+
+/// Glyph for a sprite, if it has one
+pub fn sprite_char(sprite: SpriteKind) -> Option<char> {
+    let flower1 = SpriteKind::BarrelCactus as u8..=SpriteKind::Turnip as u8;
+    let flower2 = SpriteKind::LargeGrass as u8..=SpriteKind::LargeCactus as u8;
+    let furniture = SpriteKind::Window1 as u8..=SpriteKind::WardrobeDouble as u8;
+    match sprite {
+        SpriteKind::Apple => Some('a'),
+        SpriteKind::Sunflower => Some('u'),
+        SpriteKind::Mushroom => Some('m'),
+        SpriteKind::Velorite | SpriteKind::VeloriteFrag => Some('v'),
+        SpriteKind::Chest | SpriteKind::Crate => Some('c'),
+        SpriteKind::Stones => Some('"'),
+        SpriteKind::Twigs => Some(';'),
+        SpriteKind::Amethyst | SpriteKind::Ruby => Some('☼'), // TODO: add more
+        SpriteKind::Beehive => Some('b'),
+        _ => {
+            let sprite3 = sprite as u8;
+            if flower1.contains(&sprite3) || flower2.contains(&sprite3) {
+                Some('♣')
+            } else if furniture.contains(&sprite3) {
+                match sprite {
+                    SpriteKind::Bed => Some('Θ'),
+                    SpriteKind::Bench | SpriteKind::ChairSingle | SpriteKind::ChairDouble => {
+                        Some('╥')
+                    }
+                    SpriteKind::TableSide | SpriteKind::TableDining | SpriteKind::TableDouble => {
+                        Some('╤')
+                    }
+                    _ => Some('π'),
+                }
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Glyph for a filled block
+pub fn block_char(kind: BlockKind) -> char {
+    match kind {
+        BlockKind::Air => ' ',
+        BlockKind::Water => '≈',
+        BlockKind::Rock => 'o',
+        BlockKind::WeakRock => '.',
+        BlockKind::Lava => '≈',
+        BlockKind::GlowingRock => '*',
+        BlockKind::GlowingWeakRock => '.',
+        BlockKind::Grass => ',',
+        BlockKind::Snow => '≈',
+        BlockKind::Earth => '0',
+        BlockKind::Sand => '▓',
+        BlockKind::Wood => '≡',
+        BlockKind::Leaves => '♠',
+        BlockKind::Misc => '#',
+    }
+}
+
+/// Scan down from just above `wpos` and find what should be drawn for the column
+pub fn scan_column(terrain: &TerrainGrid, wpos: Vec3<i32>) -> Cell {
+    let mut block_z = 0;
+    let mut block: Option<Block> = None;
+    let mut block_char = None;
+    let mut glow = 0;
+    let mut top_sprite = None;
+
+    for z in -2..ELEVATION {
+        let block_pos = wpos + Vec3::unit_z() * -z;
+        block_z = block_pos.z;
+
+        if let Ok(b) = terrain.get(block_pos) {
+            let sprite = b.get_sprite();
+            if sprite.is_some() && sprite.unwrap() != SpriteKind::Empty {
+                block_char = self::sprite_char(sprite.unwrap());
+                top_sprite = top_sprite.or(sprite);
+                glow = glow.max(b.get_glow().unwrap_or(0));
+            } else if b.is_filled() {
+                block = Some(*b);
+                if block_char.is_none() {
+                    block_char = Some(self::block_char(b.kind()));
+                    glow = glow.max(b.get_glow().unwrap_or(0));
+                }
+                break;
+            }
+        }
+    }
+
+    let level_down = (wpos.z - 2 - block_z) as usize;
+
+    let col: RGB = match block {
+        Some(block) => {
+            let rgb = block.get_color().unwrap();
+
+            let tint: f32 = if level_down < LEVEL_TINT.len() {
+                LEVEL_TINT[level_down as usize]
+            } else {
+                1.0
+            };
+
+            if tint != 1.0 {
+                RGB::from_u8(
+                    rgb.r + (tint * ((255 - rgb.r) as f32)) as u8,
+                    rgb.g + (tint * ((255 - rgb.g) as f32)) as u8,
+                    rgb.b + (tint * ((255 - rgb.b) as f32)) as u8,
+                )
+            } else {
+                RGB::from_u8(rgb.r, rgb.g, rgb.b)
+            }
+        }
+        None => RGB::named(YELLOW),
+    };
+
+    // Only something solid above a gap of air over the visible block is a roof, otherwise a
+    // hillside rising above the player would roof itself over. Leaves let enough light through
+    // that forests shouldn't count as caves
+    let roofed = (block_z + 1..wpos.z + ROOF_HEIGHT)
+        .map(|z| {
+            terrain
+                .get(Vec3::new(wpos.x, wpos.y, z))
+                .map_or(false, |b| b.is_filled() && b.kind() != BlockKind::Leaves)
+        })
+        .skip_while(|solid| *solid)
+        .any(|solid| solid);
+
+    Cell {
+        glyph: block_char.unwrap_or('?'),
+        col,
+        glow,
+        roofed,
+        kind: block.map(|b| b.kind()),
+        sprite: top_sprite,
+    }
+}
+
+/// How much a sprite is worth showing when it only covers part of a zoomed out cell, 0 if not
+/// at all
+pub fn sprite_rank(sprite: SpriteKind) -> u8 {
+    match sprite {
+        SpriteKind::Chest | SpriteKind::Crate => 3,
+        SpriteKind::Velorite
+        | SpriteKind::VeloriteFrag
+        | SpriteKind::Amethyst
+        | SpriteKind::Ruby => 2,
+        SpriteKind::Apple | SpriteKind::Mushroom | SpriteKind::Beehive => 1,
+        _ => 0,
+    }
+}
+
+/// Whether a column looks like part of a building
+fn is_structure(cell: &Cell) -> bool {
+    let furniture = SpriteKind::Window1 as u8..=SpriteKind::WardrobeDouble as u8;
+    matches!(cell.kind, Some(BlockKind::Wood) | Some(BlockKind::Misc))
+        || cell
+            .sprite
+            .map_or(false, |s| furniture.contains(&(s as u8)))
+}
+
+/// Find what should be drawn for a map cell covering `zoom` blocks on each side
+///
+/// Columns are fetched through `column`, normally `scan_column` or a cache in front of it. At
+/// `zoom` of 1.0 or less this is just the column at `wpos`. Further out, a grid of columns
+/// across the cell is sampled and the most informative one wins: valuable sprites first, then
+/// water and settlements when they cover a good part of the cell, then the most common block.
+pub fn scan_cell(wpos: Vec3<i32>, zoom: f32, mut column: impl FnMut(Vec3<i32>) -> Cell) -> Cell {
+    if zoom <= 1.0 {
+        return column(wpos);
+    }
+
+    let n = (zoom.ceil() as i32).min(MAX_SAMPLES);
+    let step = zoom / n as f32;
+    let mut samples = Vec::with_capacity((n * n) as usize);
+    for j in 0..n {
+        for i in 0..n {
+            let offs = Vec2::new(i as f32 * step, -(j as f32) * step).map(|e| e.floor() as i32);
+            samples.push(column(wpos + Vec3::from(offs)));
+        }
+    }
+
+    let glow = samples.iter().map(|c| c.glow).max().unwrap_or(0);
+    let roofed = samples.iter().filter(|c| c.roofed).count() * 2 > samples.len();
+    let quarter = (samples.len() + 3) / 4;
+
+    let water = samples
+        .iter()
+        .filter(|c| c.kind == Some(BlockKind::Water))
+        .count();
+    let structures = samples.iter().filter(|c| is_structure(c)).count();
+
+    let best = if let Some(cell) = samples
+        .iter()
+        .filter(|c| c.sprite.map_or(0, sprite_rank) > 0)
+        .max_by_key(|c| c.sprite.map_or(0, sprite_rank))
+    {
+        *cell
+    } else if water >= quarter {
+        *samples
+            .iter()
+            .find(|c| c.kind == Some(BlockKind::Water))
+            .unwrap()
+    } else if structures >= quarter {
+        let cell = samples.iter().find(|c| is_structure(c)).unwrap();
+        Cell {
+            glyph: '⌂',
+            ..*cell
+        }
+    } else {
+        // Most common block kind, first sample of that kind standing in for the rest
+        *samples
+            .iter()
+            .max_by_key(|c| samples.iter().filter(|o| o.kind == c.kind).count())
+            .unwrap()
+    };
+
+    Cell {
+        glow,
+        roofed,
+        ..best
+    }
+}
+
+/// Arrow pointing the way `dir` faces on the map, which has north up
+pub fn facing_glyph(dir: Vec2<f32>) -> char {
+    if dir.x.abs() > dir.y.abs() {
+        if dir.x > 0.0 {
+            '→'
+        } else {
+            '←'
+        }
+    } else if dir.y < 0.0 {
+        '↓'
+    } else {
+        '↑'
+    }
+}
+
+/// Nearest of the eight compass points to `dir`, with north up the map
+pub fn compass_point(dir: Vec2<f32>) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    let bearing = dir.x.atan2(dir.y).to_degrees().rem_euclid(360.0);
+    POINTS[(bearing / 45.0).round() as usize % POINTS.len()]
+}
+
+/// Whether an entity attacks the player on sight, enemies always and wild creatures when their
+/// species is aggressive
+pub fn is_hostile(alignment: &Alignment, body: Option<&Body>) -> bool {
+    match alignment {
+        Alignment::Enemy => true,
+        Alignment::Wild => body.map_or(false, |body| Psyche::from(body).aggro >= AGGRESSIVE),
+        _ => false,
+    }
+}
+
+/// What entities are colored by, their glyph always shows what they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntityColors {
+    /// The colors of `body_glyph`, each humanoid species its own and creatures red
+    Species,
+    /// How they stand towards the player, see `Relation`
+    Threat,
+}
+
+impl Default for EntityColors {
+    fn default() -> Self {
+        EntityColors::Threat
+    }
+}
+
+/// How an entity stands towards the player
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Me,
+    GroupMember,
+    OtherPlayer,
+    Tame,
+    Neutral,
+    Hostile,
+}
+
+impl Relation {
+    /// Relation of a creature or NPC, players and group members are told apart by the caller
+    ///
+    /// Owned creatures side with their owner, whose relation is `owner` if it's known.
+    pub fn from_alignment(alignment: &Alignment, body: Option<&Body>, owner: Option<Self>) -> Self {
+        match alignment {
+            Alignment::Owned(_) => match owner {
+                Some(Relation::Me) | Some(Relation::GroupMember) => Relation::Tame,
+                Some(Relation::Hostile) => Relation::Hostile,
+                _ => Relation::Neutral,
+            },
+            Alignment::Tame => Relation::Tame,
+            _ if is_hostile(alignment, body) => Relation::Hostile,
+            _ => Relation::Neutral,
+        }
+    }
+
+    pub fn color(self) -> RGB {
+        match self {
+            Relation::Me => RGB::named(WHITE),
+            Relation::GroupMember => RGB::named(CYAN),
+            Relation::OtherPlayer => RGB::named(LIGHTSKYBLUE),
+            Relation::Tame => RGB::named(GREEN),
+            Relation::Neutral => RGB::named(YELLOW),
+            Relation::Hostile => RGB::named(RED),
+        }
+    }
+}
+
+/// Glyph and color an entity's body is drawn with
+pub fn body_glyph(body: &Body) -> (char, RGB) {
+    match body {
+        Body::Humanoid(humanoid) => match humanoid.species {
+            humanoid::Species::Danari => ('☻', RGB::named(BROWN2)),
+            humanoid::Species::Dwarf => ('☺', RGB::named(ORANGE)),
+            humanoid::Species::Elf => ('☺', RGB::named(BLUE)),
+            humanoid::Species::Human => ('☺', RGB::named(IVORY)),
+            humanoid::Species::Orc => ('☻', RGB::named(GREEN)),
+            humanoid::Species::Undead => ('☻', RGB::named(WHITE)),
+        },
+        Body::QuadrupedLow(_) => ('4', RGB::named(RED)),
+        Body::QuadrupedSmall(_) => ('q', RGB::named(RED)),
+        Body::QuadrupedMedium(_) => ('Q', RGB::named(RED)),
+        Body::BirdMedium(_) => ('b', RGB::named(RED)),
+        Body::BirdLarge(_) => ('B', RGB::named(RED)),
+        Body::FishSmall(_) => ('f', RGB::named(RED)),
+        Body::FishMedium(_) => ('F', RGB::named(RED)),
+        Body::BipedLarge(_) => ('2', RGB::named(RED)),
+        Body::BipedSmall(_) => ('2', RGB::named(RED)),
+        Body::Object(_) => ('◙', RGB::named(YELLOW)),
+        Body::Golem(_) => ('G', RGB::named(TAN)),
+        Body::Dragon(_) => ('₧', RGB::named(RED)),
+        Body::Theropod(_) => ('T', RGB::named(RED)),
+        Body::Ship(_) => ('S', RGB::named(BROWN1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heightmap;
+    use veloren_common::{uid::Uid, vol::WriteVol};
+
+    #[test]
+    fn hillsides_are_not_their_own_roof() {
+        let mut terrain = heightmap::tests::terrain();
+        let rock = Block::new(BlockKind::Rock, Rgb::new(100, 100, 100));
+        // A hill rising well above the player just east of them
+        for y in 0..4 {
+            for x in 3..7 {
+                for z in 0..20 {
+                    terrain.set(Vec3::new(x, y, z), rock).unwrap();
+                }
+            }
+        }
+        // And a ledge over open ground to the west
+        for y in 0..4 {
+            for x in -6..-2 {
+                terrain.set(Vec3::new(x, y, 12), rock).unwrap();
+            }
+        }
+
+        let player_z = 6;
+        for y in 0..4 {
+            let hill = scan_column(&terrain, Vec3::new(4, y, player_z));
+            assert_eq!(hill.kind, Some(BlockKind::Rock));
+            assert!(!hill.roofed, "hillside at y {} is roofed", y);
+            assert!(scan_column(&terrain, Vec3::new(-4, y, player_z)).roofed);
+            assert!(!scan_column(&terrain, Vec3::new(-10, y, player_z)).roofed);
+        }
+    }
+
+    #[test]
+    fn compass_points_have_north_up() {
+        for (dir, point) in &[
+            (Vec2::new(0.0, 1.0), "N"),
+            (Vec2::new(1.0, 1.0), "NE"),
+            (Vec2::new(1.0, 0.0), "E"),
+            (Vec2::new(1.0, -1.0), "SE"),
+            (Vec2::new(0.0, -1.0), "S"),
+            (Vec2::new(-1.0, -1.0), "SW"),
+            (Vec2::new(-1.0, 0.0), "W"),
+            (Vec2::new(-1.0, 1.0), "NW"),
+            (Vec2::new(-0.1, 1.0), "N"),
+            (Vec2::new(0.3, 1.0), "N"),
+        ] {
+            assert_eq!(compass_point(*dir), *point, "{:?}", dir);
+        }
+    }
+
+    #[test]
+    fn facing_glyphs_point_the_nearest_way() {
+        assert_eq!(facing_glyph(Vec2::new(1.0, 0.2)), '→');
+        assert_eq!(facing_glyph(Vec2::new(-1.0, -0.2)), '←');
+        assert_eq!(facing_glyph(Vec2::new(0.2, 1.0)), '↑');
+        assert_eq!(facing_glyph(Vec2::new(-0.2, -1.0)), '↓');
+    }
+
+    #[test]
+    fn pets_side_with_their_owner() {
+        let owned = Alignment::Owned(Uid(1));
+        for (owner, relation) in &[
+            (Some(Relation::Me), Relation::Tame),
+            (Some(Relation::GroupMember), Relation::Tame),
+            (Some(Relation::OtherPlayer), Relation::Neutral),
+            (Some(Relation::Hostile), Relation::Hostile),
+            (None, Relation::Neutral),
+        ] {
+            assert_eq!(Relation::from_alignment(&owned, None, *owner), *relation);
+        }
+    }
+
+    #[test]
+    fn creatures_of_unknown_species_are_not_hostile_unless_enemies() {
+        assert_eq!(
+            Relation::from_alignment(&Alignment::Enemy, None, None),
+            Relation::Hostile
+        );
+        assert_eq!(
+            Relation::from_alignment(&Alignment::Wild, None, None),
+            Relation::Neutral
+        );
+        assert_eq!(
+            Relation::from_alignment(&Alignment::Tame, None, None),
+            Relation::Tame
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use crate::{
+    keymap::{Keymap, MovementPreset},
+    layout::LayoutPreset,
+    render::EntityColors,
+};
+
+/// Smallest console in characters the map and HUD panels still fit in
+pub const MIN_SIZE: (u32, u32) = (40, 25);
+
+/// User settings, read from a RON file next to the executable's working directory
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Console width in characters
+    pub width: u32,
+    /// Console height in characters
+    pub height: u32,
+    /// Font of the HUD layer, either built into bracket-terminal or a file in `resources/`
+    pub hud_font: String,
+    /// Glyph size of `hud_font` in pixels, smaller than 8x8 fits more text on the HUD
+    pub hud_font_size: (u32, u32),
+    /// Where the map and HUD panels go
+    pub layout: LayoutPreset,
+    /// Whether entities are colored by species or by how they stand towards you
+    pub entity_colors: EntityColors,
+    /// Directory the map memory of each server and character is kept in
+    pub data_dir: String,
+    /// Sets of movement keys to use, on top of any movement keys in `keymap`
+    pub movement: Vec<MovementPreset>,
+    /// Keys bound to each action, actions left out keep their default keys
+    pub keymap: Keymap,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            height: 50,
+            hud_font: "terminal8x8.png".to_string(),
+            hud_font_size: (8, 8),
+            layout: LayoutPreset::default(),
+            entity_colors: EntityColors::default(),
+            data_dir: "velobracket-data".to_string(),
+            movement: vec![MovementPreset::Wasd, MovementPreset::Numpad],
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Load settings from `path`, writing out the defaults if there is no file yet
+    ///
+    /// A file that fails to parse is left alone so it can be fixed by hand, and the defaults are
+    /// used meanwhile.
+    pub fn load(path: &Path) -> Self {
+        let mut settings = match Self::read(path) {
+            Ok(Some(settings)) => settings,
+            Ok(None) => {
+                let settings = Self::default();
+                settings.save(path);
+                settings
+            }
+            Err(err) => {
+                println!("{}, using default settings", err);
+                Self::default()
+            }
+        };
+        settings.validate();
+        settings.keymap.set_movement(&settings.movement);
+        settings
+    }
+
+    /// Replace values the console can't be built with by their defaults, saying which
+    pub fn validate(&mut self) {
+        let default = Self::default();
+        if self.width < MIN_SIZE.0 || self.height < MIN_SIZE.1 {
+            println!(
+                "Console size {}x{} is smaller than {}x{}, using {}x{}",
+                self.width, self.height, MIN_SIZE.0, MIN_SIZE.1, default.width, default.height
+            );
+            self.width = default.width;
+            self.height = default.height;
+        }
+        if self.hud_font_size.0 == 0 || self.hud_font_size.1 == 0 {
+            println!(
+                "HUD font size {}x{} has no area, using {} at {}x{}",
+                self.hud_font_size.0,
+                self.hud_font_size.1,
+                default.hud_font,
+                default.hud_font_size.0,
+                default.hud_font_size.1
+            );
+            self.hud_font = default.hud_font;
+            self.hud_font_size = default.hud_font_size;
+        }
+    }
+
+    /// Settings as they are in the file at `path`, `None` if there is no file
+    pub fn read(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => ron::de::from_str(&contents)
+                .map(Some)
+                .map_err(|err| format!("Failed to parse {}: {}", path.display(), err)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => {
+                if let Err(err) = fs::write(path, contents) {
+                    println!("Failed to save {}: {}", path.display(), err);
+                }
+            }
+            Err(err) => println!("Failed to serialize settings: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unusable_sizes_fall_back_to_defaults() {
+        let mut settings = Settings {
+            width: 10,
+            height: 50,
+            hud_font: "terminal6x6.png".to_string(),
+            hud_font_size: (0, 6),
+            ..Settings::default()
+        };
+        settings.validate();
+        assert_eq!((settings.width, settings.height), (80, 50));
+        assert_eq!(settings.hud_font, "terminal8x8.png");
+        assert_eq!(settings.hud_font_size, (8, 8));
+    }
+
+    #[test]
+    fn usable_sizes_are_kept() {
+        let mut settings = Settings {
+            width: 120,
+            height: 40,
+            hud_font: "terminal6x6.png".to_string(),
+            hud_font_size: (6, 6),
+            ..Settings::default()
+        };
+        settings.validate();
+        assert_eq!((settings.width, settings.height), (120, 40));
+        assert_eq!(settings.hud_font_size, (6, 6));
+    }
+}
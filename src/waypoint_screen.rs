@@ -0,0 +1,162 @@
+use bracket_terminal::prelude::*;
+use vek::*;
+
+use crate::{
+    hud,
+    keymap::{Action, Keymap},
+    waypoints::Waypoints,
+};
+
+/// What the waypoint screen wants done after a key press
+pub enum WaypointEvent {
+    None,
+    /// Find the way to the waypoint at this index
+    Travel(usize),
+    /// Post the waypoint at this index to group chat
+    Share(usize),
+    Close,
+}
+
+/// List of waypoints with how far away they are, where they can be tracked, renamed or removed
+#[derive(Default)]
+pub struct WaypointScreen {
+    cursor: usize,
+    /// New name of the selected waypoint while it's being typed
+    naming: Option<String>,
+}
+
+impl WaypointScreen {
+    /// Act on a pressed key and the actions bound to it, names are typed with the raw keys
+    pub fn handle_key(
+        &mut self,
+        key: VirtualKeyCode,
+        actions: &[Action],
+        waypoints: &mut Waypoints,
+    ) -> WaypointEvent {
+        let count = waypoints.list().len();
+        self.cursor = self.cursor.min(count.saturating_sub(1));
+
+        // Letters are typed in through `type_text` while naming
+        if let Some(name) = self.naming.as_mut() {
+            match key {
+                VirtualKeyCode::Return => {
+                    if !name.trim().is_empty() {
+                        waypoints.rename(self.cursor, name.trim().to_string());
+                    }
+                    self.naming = None;
+                }
+                VirtualKeyCode::Back => {
+                    name.pop();
+                }
+                VirtualKeyCode::Escape => self.naming = None,
+                _ => {}
+            }
+            return WaypointEvent::None;
+        }
+
+        if key == VirtualKeyCode::Escape {
+            return WaypointEvent::Close;
+        }
+        for action in actions {
+            match action {
+                Action::MenuUp => self.cursor = self.cursor.saturating_sub(1),
+                Action::MenuDown => self.cursor = (self.cursor + 1).min(count.saturating_sub(1)),
+                _ if count == 0 => {}
+                Action::MenuSelect => {
+                    waypoints.tracked = match waypoints.tracked {
+                        Some(i) if i == self.cursor => None,
+                        _ => Some(self.cursor),
+                    }
+                }
+                Action::WaypointTravel => return WaypointEvent::Travel(self.cursor),
+                Action::WaypointShare => return WaypointEvent::Share(self.cursor),
+                Action::WaypointRename => self.naming = Some(String::new()),
+                Action::WaypointRemove => {
+                    waypoints.remove(self.cursor);
+                    return WaypointEvent::None;
+                }
+                _ => {}
+            }
+        }
+        WaypointEvent::None
+    }
+
+    /// Add typed text to the name being typed, if any
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(name) = self.naming.as_mut() {
+            name.push_str(text);
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut BTerm,
+        rect: Rect,
+        waypoints: &Waypoints,
+        player_pos: Vec3<f32>,
+        keymap: &Keymap,
+    ) {
+        if !hud::draw_panel(ctx, rect, "Waypoints") {
+            return;
+        }
+
+        if waypoints.list().is_empty() {
+            ctx.print_color(
+                rect.x1 + 2,
+                rect.y1 + 1,
+                RGB::named(GREY),
+                RGB::named(BLACK),
+                &hud::fit("No waypoints yet", rect.width() - 4),
+            );
+        }
+
+        let rows = (rect.height() - 4).max(0) as usize;
+        let first = (self.cursor + 1).saturating_sub(rows);
+        for (i, waypoint) in waypoints.list().iter().enumerate().skip(first).take(rows) {
+            let name = match &self.naming {
+                Some(name) if i == self.cursor => format!("{}_", name),
+                _ => waypoint.name.clone(),
+            };
+            let tracked = if waypoints.tracked == Some(i) {
+                '♦'
+            } else {
+                ' '
+            };
+            let line = format!("{} {:<24}{}", tracked, name, waypoint.describe(player_pos));
+            let (fg, bg) = if i == self.cursor {
+                (RGB::named(BLACK), RGB::named(WHITE))
+            } else {
+                (RGB::named(WHITE), RGB::named(BLACK))
+            };
+            ctx.print_color(
+                rect.x1 + 2,
+                rect.y1 + 1 + (i - first) as i32,
+                fg,
+                bg,
+                &hud::fit(&line, rect.width() - 4),
+            );
+        }
+
+        let help = if self.naming.is_some() {
+            "Type a name  Enter - Done  Esc - Cancel".to_string()
+        } else {
+            format!(
+                "{}/{} - Select  {} - Track  {} - Go  {} - Rename  {} - Share  {} - Remove  Esc - Close",
+                keymap.label(Action::MenuUp),
+                keymap.label(Action::MenuDown),
+                keymap.label(Action::MenuSelect),
+                keymap.label(Action::WaypointTravel),
+                keymap.label(Action::WaypointRename),
+                keymap.label(Action::WaypointShare),
+                keymap.label(Action::WaypointRemove),
+            )
+        };
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y2 - 2,
+            RGB::named(GREY),
+            RGB::named(BLACK),
+            &hud::fit(&help, rect.width() - 4),
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use vek::*;
+
+use crate::{memory, render};
+
+/// A named place to find the way back to
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub name: String,
+    pub pos: Vec2<i32>,
+    /// Height of the place, unknown for waypoints put on the world map or shared without one
+    pub alt: Option<i32>,
+}
+
+impl Waypoint {
+    /// Middle of the block the waypoint is on
+    pub fn center(&self) -> Vec2<f32> {
+        self.pos.map(|e| e as f32 + 0.5)
+    }
+
+    /// How far and which way the waypoint is from `from`, such as `120 m NE, 12 up`
+    pub fn describe(&self, from: Vec3<f32>) -> String {
+        let offset = self.center() - Vec2::from(from);
+        let distance = offset.magnitude();
+        let mut text = if distance < 1.0 {
+            "here".to_string()
+        } else {
+            format!("{:.0} m {}", distance, render::compass_point(offset))
+        };
+        if let Some(alt) = self.alt {
+            let climb = alt - from.z.floor() as i32;
+            if climb > 0 {
+                text.push_str(&format!(", {} up", climb));
+            } else if climb < 0 {
+                text.push_str(&format!(", {} down", -climb));
+            }
+        }
+        text
+    }
+
+    /// Chat message `parse` turns back into this waypoint
+    pub fn share_message(&self) -> String {
+        match self.alt {
+            Some(alt) => format!("{} @ {}, {}, {}", self.name, self.pos.x, self.pos.y, alt),
+            None => format!("{} @ {}, {}", self.name, self.pos.x, self.pos.y),
+        }
+    }
+}
+
+/// Waypoint in a chat message written like `share_message` does, such as
+/// `Camp @ 1200, -340, 96`
+///
+/// Coordinates are two or three whole numbers separated by commas right after an `@`, whatever
+/// comes before it is the name, which is left empty if there's nothing.
+pub fn parse(message: &str) -> Option<Waypoint> {
+    message
+        .match_indices('@')
+        .find_map(|(i, _)| coordinates(message[i + 1..].trim_start()).map(|coords| (i, coords)))
+        .map(|(i, coords)| Waypoint {
+            name: message[..i].trim().to_string(),
+            pos: Vec2::new(coords[0], coords[1]),
+            alt: coords.get(2).copied(),
+        })
+}
+
+/// Two or three comma separated numbers at the start of `text`
+fn coordinates(text: &str) -> Option<Vec<i32>> {
+    let (first, mut rest) = number(text)?;
+    let mut coords = vec![first];
+    while coords.len() < 3 {
+        match rest
+            .trim_start()
+            .strip_prefix(',')
+            .and_then(|rest| number(rest.trim_start()))
+        {
+            Some((n, after)) => {
+                coords.push(n);
+                rest = after;
+            }
+            None => break,
+        }
+    }
+    if coords.len() >= 2 && !rest.starts_with(|c: char| c.is_alphanumeric()) {
+        Some(coords)
+    } else {
+        None
+    }
+}
+
+/// Whole number at the start of `text` and the text after it
+fn number(text: &str) -> Option<(i32, &str)> {
+    let end = text
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+        .map_or(text.len(), |(i, _)| i);
+    text[..end].parse().ok().map(|n| (n, &text[end..]))
+}
+
+/// File the waypoints of `server` are kept in, under `data_dir`
+pub fn path(data_dir: &Path, server: &str) -> PathBuf {
+    data_dir.join(format!("{}.waypoints.ron", memory::file_name(server)))
+}
+
+/// Every waypoint placed on a server, saved whenever they change
+pub struct Waypoints {
+    path: PathBuf,
+    list: Vec<Waypoint>,
+    /// Waypoint whose way is shown on the HUD
+    pub tracked: Option<usize>,
+}
+
+impl Waypoints {
+    /// Read the waypoints from `path`, starting with none if there's no file yet
+    pub fn load(path: PathBuf) -> Self {
+        let list = match fs::read_to_string(&path) {
+            Ok(contents) => ron::de::from_str(&contents).unwrap_or_else(|err| {
+                println!("Failed to parse {}: {}, starting over", path.display(), err);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            list,
+            tracked: None,
+        }
+    }
+
+    fn save(&self) {
+        if let Some(dir) = self.path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                println!("Failed to create {}: {}", dir.display(), err);
+                return;
+            }
+        }
+        match ron::ser::to_string_pretty(&self.list, ron::ser::PrettyConfig::default()) {
+            Ok(contents) => {
+                if let Err(err) = fs::write(&self.path, contents) {
+                    println!("Failed to save {}: {}", self.path.display(), err);
+                }
+            }
+            Err(err) => println!("Failed to serialize waypoints: {}", err),
+        }
+    }
+
+    pub fn list(&self) -> &[Waypoint] {
+        &self.list
+    }
+
+    pub fn tracked(&self) -> Option<&Waypoint> {
+        self.tracked.and_then(|i| self.list.get(i))
+    }
+
+    /// First `Marker N` name not taken yet
+    pub fn next_name(&self) -> String {
+        (1..)
+            .map(|n| format!("Marker {}", n))
+            .find(|name| self.list.iter().all(|waypoint| waypoint.name != *name))
+            .unwrap()
+    }
+
+    /// Add a waypoint, unless there already is one at the same place
+    ///
+    /// Returns whether it was added.
+    pub fn add(&mut self, waypoint: Waypoint) -> bool {
+        if self.list.iter().any(|other| other.pos == waypoint.pos) {
+            return false;
+        }
+        self.list.push(waypoint);
+        self.save();
+        true
+    }
+
+    pub fn rename(&mut self, i: usize, name: String) {
+        if let Some(waypoint) = self.list.get_mut(i) {
+            waypoint.name = name;
+            self.save();
+        }
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        if i >= self.list.len() {
+            return;
+        }
+        self.list.remove(i);
+        self.tracked = match self.tracked {
+            Some(tracked) if tracked == i => None,
+            Some(tracked) if tracked > i => Some(tracked - 1),
+            tracked => tracked,
+        };
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waypoint(name: &str, pos: Vec2<i32>, alt: Option<i32>) -> Waypoint {
+        Waypoint {
+            name: name.to_string(),
+            pos,
+            alt,
+        }
+    }
+
+    #[test]
+    fn shared_waypoints_parse_back() {
+        for shared in &[
+            waypoint("Camp", Vec2::new(1200, -340), Some(96)),
+            waypoint("Mine 2", Vec2::new(-5, 7), None),
+        ] {
+            let parsed = parse(&shared.share_message()).unwrap();
+            assert_eq!(parsed.name, shared.name);
+            assert_eq!(parsed.pos, shared.pos);
+            assert_eq!(parsed.alt, shared.alt);
+        }
+    }
+
+    #[test]
+    fn coordinates_need_an_at_sign() {
+        assert!(parse("need 2, 3 more potions").is_none());
+        assert!(parse("meet at 1200, -340").is_none());
+    }
+
+    #[test]
+    fn name_is_whatever_comes_before_the_at_sign() {
+        let parsed = parse("@ 10,20").unwrap();
+        assert_eq!(parsed.name, "");
+        assert_eq!(parsed.pos, Vec2::new(10, 20));
+
+        let parsed = parse("mail me@home then Cave 3 @ -1, -2, -3 see you").unwrap();
+        assert_eq!(parsed.name, "mail me@home then Cave 3");
+        assert_eq!(parsed.pos, Vec2::new(-1, -2));
+        assert_eq!(parsed.alt, Some(-3));
+    }
+
+    #[test]
+    fn coordinates_are_two_or_three_whole_numbers() {
+        assert_eq!(coordinates("1, 2"), Some(vec![1, 2]));
+        assert_eq!(coordinates("-1,-2 ,-3"), Some(vec![-1, -2, -3]));
+        assert_eq!(coordinates("1, 2, 3, 4"), Some(vec![1, 2, 3]));
+        assert_eq!(coordinates("1, 2."), Some(vec![1, 2]));
+        assert_eq!(coordinates("1"), None);
+        assert_eq!(coordinates("1, -"), None);
+        assert_eq!(coordinates("-, 1, 2"), None);
+        assert_eq!(coordinates("1, 2x"), None);
+        assert_eq!(coordinates("1.5, 2"), None);
+    }
+
+    #[test]
+    fn numbers_stop_at_the_first_non_digit() {
+        assert_eq!(number("-12abc"), Some((-12, "abc")));
+        assert_eq!(number("-"), None);
+        assert_eq!(number("1-2"), Some((1, "-2")));
+        assert_eq!(number(""), None);
+    }
+}
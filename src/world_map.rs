@@ -0,0 +1,251 @@
+use bracket_terminal::prelude::*;
+use vek::*;
+
+use veloren_common::{grid::Grid, terrain::TerrainChunkSize, vol::RectVolSize};
+use veloren_common_net::msg::world_msg::{SiteInfo, SiteKind};
+
+use crate::{
+    hud,
+    keymap::{Action, Keymap},
+};
+
+/// Closest zoom, one chunk to a cell
+const MIN_SCALE: f32 = 32.0;
+/// Farthest zoom
+const MAX_SCALE: f32 = 1024.0;
+/// Cells between the cursor and the edge of the map before it scrolls
+const SCROLL_MARGIN: i32 = 4;
+
+/// Glyph and color of a part of the world from its map color and height, 0 at the bottom of
+/// the world and 1 at the top
+fn terrain_glyph(color: u32, alt: f32) -> (char, RGB) {
+    let [r, g, b, _] = color.to_le_bytes();
+    let col = RGB::from_u8(r, g, b);
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let brightness = (r + g + b) / (3.0 * 255.0);
+    let glyph = if b > r && b > g {
+        '≈'
+    } else if alt > 0.6 {
+        '▲'
+    } else if alt > 0.4 {
+        '∩'
+    } else if g > r && g > b && brightness < 0.3 {
+        '♣'
+    } else if g > r && g > b {
+        '"'
+    } else {
+        '.'
+    };
+    (glyph, col)
+}
+
+fn site_glyph(kind: &SiteKind) -> (char, RGB) {
+    match kind {
+        SiteKind::Town => ('#', RGB::named(YELLOW)),
+        SiteKind::Castle => ('π', RGB::named(WHITE)),
+        SiteKind::Dungeon { .. } => ('Ω', RGB::named(RED)),
+        SiteKind::Cave => ('O', RGB::named(GREY)),
+        _ => ('*', RGB::named(CYAN)),
+    }
+}
+
+/// Whether a site is a town, castle or dungeon, which are worth giving directions by
+pub fn is_landmark(kind: &SiteKind) -> bool {
+    matches!(
+        kind,
+        SiteKind::Town | SiteKind::Castle | SiteKind::Dungeon { .. }
+    )
+}
+
+/// Name of a site, or its kind if it has none
+pub fn site_name(site: &SiteInfo) -> String {
+    match &site.name {
+        Some(name) => name.clone(),
+        None => match site.kind {
+            SiteKind::Dungeon { difficulty } => format!("Dungeon (difficulty {})", difficulty),
+            ref kind => format!("{:?}", kind),
+        },
+    }
+}
+
+/// Full-screen map of the whole world as the server describes it, with a cursor to look around
+pub struct WorldMap {
+    /// World position in the middle of the screen
+    center: Vec2<f32>,
+    /// World position under the cursor
+    cursor: Vec2<f32>,
+    /// Blocks across each cell
+    scale: f32,
+    /// Height of the highest chunk in the world, which doesn't change while playing
+    max_alt: f32,
+}
+
+impl WorldMap {
+    /// Map centered on the player, `lod_alt` is the height of each chunk the server sent when
+    /// we registered
+    pub fn new(player_pos: Vec2<f32>, lod_alt: &Grid<u32>) -> Self {
+        // Heights are the low 16 bits of each chunk's altitude
+        let max_alt = lod_alt
+            .iter()
+            .map(|(_, alt)| alt & 0xFFFF)
+            .max()
+            .unwrap_or(1)
+            .max(1) as f32;
+        Self {
+            center: player_pos,
+            cursor: player_pos,
+            scale: MIN_SCALE * 4.0,
+            max_alt,
+        }
+    }
+
+    pub fn cursor(&self) -> Vec2<f32> {
+        self.cursor
+    }
+
+    /// Move the cursor with the arrow keys and zoom with `+`/`-`, `Home` goes back to the player
+    ///
+    /// Returns true if the map should be closed.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, player_pos: Vec2<f32>) -> bool {
+        match key {
+            VirtualKeyCode::Up => self.cursor.y += self.scale,
+            VirtualKeyCode::Down => self.cursor.y -= self.scale,
+            VirtualKeyCode::Left => self.cursor.x -= self.scale,
+            VirtualKeyCode::Right => self.cursor.x += self.scale,
+            VirtualKeyCode::Plus => {
+                self.scale = (self.scale / 2.0).max(MIN_SCALE);
+                self.center = self.cursor;
+            }
+            VirtualKeyCode::Minus => {
+                self.scale = (self.scale * 2.0).min(MAX_SCALE);
+                self.center = self.cursor;
+            }
+            VirtualKeyCode::Home => {
+                self.cursor = player_pos;
+                self.center = player_pos;
+            }
+            VirtualKeyCode::Escape => return true,
+            _ => {}
+        }
+        false
+    }
+
+    /// Draw the map over `rect`, scrolling it along if the cursor got too close to the edge
+    ///
+    /// `lod_base` and `lod_alt` are the color and height of each chunk the server sent when we
+    /// registered, the same `lod_alt` the map was made with.
+    pub fn draw(
+        &mut self,
+        ctx: &mut BTerm,
+        rect: Rect,
+        lod_base: &Grid<u32>,
+        lod_alt: &Grid<u32>,
+        sites: &[&SiteInfo],
+        markers: &[(Vec2<f32>, char, RGB)],
+        keymap: &Keymap,
+    ) {
+        if !hud::draw_panel(ctx, rect, "World map") {
+            return;
+        }
+        // Room for the cursor info and help at the bottom
+        let size = Vec2::new(rect.width() - 2, rect.height() - 4);
+        let origin = Vec2::new(rect.x1 + 1, rect.y1 + 1);
+        if size.reduce_min() <= 0 {
+            return;
+        }
+
+        let scale = self.scale;
+        let to_cell = |center: Vec2<f32>, pos: Vec2<f32>| {
+            ((pos - center) / scale * Vec2::new(1.0, -1.0)).map(|e| e.floor() as i32) + size / 2
+        };
+        let cursor = to_cell(self.center, self.cursor);
+        if cursor
+            .map2(size, |e, sz| e < SCROLL_MARGIN || e >= sz - SCROLL_MARGIN)
+            .reduce_or()
+        {
+            self.center = self.cursor;
+        }
+        let in_view = |cell: Vec2<i32>| cell.map2(size, |e, sz| e >= 0 && e < sz).reduce_and();
+
+        let chunk_size = TerrainChunkSize::RECT_SIZE.map(|e| e as f32);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let offs =
+                    (Vec2::new(x, y) - size / 2).map(|e| e as f32 + 0.5) * Vec2::new(1.0, -1.0);
+                let chunk =
+                    ((self.center + offs * self.scale) / chunk_size).map(|e| e.floor() as i32);
+                if let (Some(color), Some(alt)) = (lod_base.get(chunk), lod_alt.get(chunk)) {
+                    let (glyph, col) = terrain_glyph(*color, (alt & 0xFFFF) as f32 / self.max_alt);
+                    ctx.print_color(origin.x + x, origin.y + y, col, RGB::named(BLACK), glyph);
+                }
+            }
+        }
+
+        // Sites, with their names once zoomed in far enough for them to fit
+        for site in sites {
+            let cell = to_cell(self.center, site.wpos.map(|e| e as f32));
+            if !in_view(cell) {
+                continue;
+            }
+            let (glyph, col) = site_glyph(&site.kind);
+            ctx.print_color(
+                origin.x + cell.x,
+                origin.y + cell.y,
+                col,
+                RGB::named(BLACK),
+                glyph,
+            );
+            if self.scale <= MIN_SCALE * 2.0 && site.name.is_some() {
+                ctx.print_color(
+                    origin.x + cell.x + 1,
+                    origin.y + cell.y,
+                    RGB::named(WHITE),
+                    RGB::named(BLACK),
+                    &hud::fit(&site_name(site), size.x - cell.x - 1),
+                );
+            }
+        }
+
+        for (pos, glyph, col) in markers {
+            let cell = to_cell(self.center, *pos);
+            if in_view(cell) {
+                ctx.print_color(
+                    origin.x + cell.x,
+                    origin.y + cell.y,
+                    *col,
+                    RGB::named(BLACK),
+                    *glyph,
+                );
+            }
+        }
+
+        let cursor = to_cell(self.center, self.cursor);
+        ctx.set_bg(origin.x + cursor.x, origin.y + cursor.y, RGB::named(GREY));
+
+        // What's under the cursor
+        let site = sites
+            .iter()
+            .find(|site| to_cell(self.center, site.wpos.map(|e| e as f32)) == cursor);
+        let mut info = format!(
+            "#[pink]Pos: #[]{:.0}, {:.0}  #[pink]Zoom: #[]{:.0} blocks",
+            self.cursor.x, self.cursor.y, self.scale
+        );
+        if let Some(site) = site {
+            info.push_str(&format!("  #[pink]Site: #[]{}", site_name(site)));
+        }
+        ctx.printer(rect.x1 + 2, rect.y2 - 3, &info, TextAlign::Left, None);
+        ctx.print_color(
+            rect.x1 + 2,
+            rect.y2 - 2,
+            RGB::named(GREY),
+            RGB::named(BLACK),
+            &hud::fit(
+                &format!(
+                    "Arrows - Cursor  +/- - Zoom  Home - You  {} - Waypoint  Esc - Close",
+                    keymap.label(Action::AddWaypoint)
+                ),
+                rect.width() - 4,
+            ),
+        );
+    }
+}